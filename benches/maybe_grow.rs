@@ -0,0 +1,52 @@
+//! Measures the cost of the stack checks performed by `stacker`.
+//!
+//! Run with `cargo bench`.
+
+extern crate stacker;
+
+use std::time::{Duration, Instant};
+
+const ITERATIONS: u32 = 100_000_000;
+
+#[inline(never)]
+fn __stacker_black_box(p: *const u8) {
+    unsafe { std::ptr::read_volatile(&p) };
+}
+
+fn bench(name: &str, mut f: impl FnMut()) {
+    // Warm up, so that the stack limit of the thread has been looked up already.
+    for _ in 0..ITERATIONS / 100 {
+        f();
+    }
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "{:<24} {:>8.3} ns/iter",
+        name,
+        nanos(elapsed) / f64::from(ITERATIONS)
+    );
+}
+
+fn nanos(d: Duration) -> f64 {
+    d.as_secs() as f64 * 1e9 + f64::from(d.subsec_nanos())
+}
+
+fn main() {
+    let x = 0u8;
+    bench("baseline", || __stacker_black_box(&x));
+    bench("remaining_stack", || {
+        let remaining = stacker::remaining_stack();
+        __stacker_black_box(&remaining as *const _ as *const u8);
+    });
+    bench("maybe_grow", || {
+        stacker::maybe_grow(32 * 1024, 1024 * 1024, || __stacker_black_box(&x))
+    });
+    stacker::StackCtx::with(|cx| {
+        bench("StackCtx::maybe_grow", || {
+            cx.maybe_grow(32 * 1024, 1024 * 1024, |_| __stacker_black_box(&x))
+        });
+    });
+}
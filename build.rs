@@ -2,6 +2,39 @@ use std::env;
 
 fn main() {
     let target = env::var("TARGET").unwrap();
+
+    // Stack switches have to be announced to the sanitizers. `cfg(sanitize)` itself is unstable,
+    // so it is mirrored into cfgs that can be used on stable compilers as well.
+    let sanitizers = env::var("CARGO_CFG_SANITIZE").unwrap_or_default();
+    for sanitizer in sanitizers.split(',') {
+        match sanitizer {
+            "address" => println!("cargo:rustc-cfg=stacker_asan"),
+            "thread" => println!("cargo:rustc-cfg=stacker_tsan"),
+            _ => {}
+        }
+    }
+    // Coroutines switch between stacks with `psm::swap_context`, which is not available on all
+    // targets. The build script of psm reports `psm::CAN_SWITCH_CONTEXT` as this variable.
+    if env::var("DEP_PSM_0_1_SWITCHABLE_CONTEXT").as_deref() == Ok("1") {
+        println!("cargo:rustc-cfg=stacker_coroutines");
+    }
+    // Panics unwind natively across stack switches with `psm::on_stack_unwind` where psm has the
+    // unwind information for it, and are caught and resumed on the original stack elsewhere.
+    if env::var("DEP_PSM_0_1_UNWINDABLE_STACK").as_deref() == Ok("1") {
+        println!("cargo:rustc-cfg=stacker_unwind");
+    }
+    // `grow` runs the closure on the current stack where psm cannot switch stacks, other than on
+    // Windows, which has a backend of its own. The tests relying on growth are ignored then. Miri
+    // does not grow the stack either, but it does not limit the size of the stack.
+    let miri = env::var_os("CARGO_CFG_MIRI").is_some();
+    let switchable_stack = env::var("DEP_PSM_0_1_SWITCHABLE_STACK").as_deref() == Ok("1");
+    if !miri && !switchable_stack && !target.contains("windows") {
+        println!("cargo:rustc-cfg=stacker_no_growth");
+    }
+    // Whether the code is built with frame pointers, only for the tests to be ignored without them.
+    if env::var("DEP_PSM_0_1_FRAME_POINTERS").as_deref() == Ok("1") {
+        println!("cargo:rustc-cfg=stacker_frame_pointers");
+    }
     let mut cfg = cc::Build::new();
     if target.contains("windows") {
         cfg.define("WINDOWS", None);
@@ -46,6 +46,8 @@ fn find_assembly(
         ("s390x", _, _, _) => Some(("src/arch/zseries_linux.s", true)),
         ("mips", _, _, _) => Some(("src/arch/mips_eabi.s", true)),
         ("mips64", _, _, _) => Some(("src/arch/mips64_eabi.s", true)),
+        ("mips32r6", _, _, _) => Some(("src/arch/mips_eabi.s", true)),
+        ("mips64r6", _, _, _) => Some(("src/arch/mips64_eabi.s", true)),
         ("sparc64", _, _, _) => Some(("src/arch/sparc64.s", true)),
         ("sparc", _, _, _) => Some(("src/arch/sparc_sysv.s", true)),
         ("riscv32", _, _, _) => Some(("src/arch/riscv.s", true)),
@@ -56,13 +58,142 @@ fn find_assembly(
     }
 }
 
+/// Whether the assembly for the target implements `rust_psm_swap_context` and
+/// `rust_psm_init_context`.
+fn has_context_switch(arch: &str, os: &str) -> bool {
+    os != "windows" && matches!(arch, "x86" | "x86_64" | "aarch64" | "riscv64")
+}
+
+/// Whether the routines for the target are implemented as naked functions in `src/naked`, which
+/// makes the assembly files unnecessary. This is limited to the Linux and Android targets the naked
+/// functions are tested on.
+fn has_naked_functions(arch: &str, os: &str) -> bool {
+    matches!(os, "linux" | "android")
+        && matches!(arch, "x86" | "x86_64" | "aarch64" | "arm" | "riscv64")
+}
+
+/// Whether `frame_pointer` and `return_address` are implemented by the assembly for the target.
+/// This requires the frame records to consist of the saved frame pointer next to the return
+/// address, which is not the case for the Win64 calling convention, so the files for Windows do not
+/// implement them.
+fn has_frame_information(arch: &str, os: &str) -> bool {
+    !matches!(os, "windows" | "cygwin" | "uefi")
+        && matches!(
+            arch,
+            "x86" | "x86_64" | "aarch64" | "riscv32" | "riscv64" | "loongarch64"
+        )
+}
+
+/// Whether the stack switching routine for the target has the unwind information needed to unwind
+/// from the new stack back to the original one. Unwinding through incorrect CFI is undefined
+/// behaviour, so this is limited to the architectures whose unwinding is tested. The other routines
+/// may well have correct CFI, but it has not been verified.
+fn has_unwind_information(arch: &str, os: &str) -> bool {
+    !matches!(os, "windows" | "cygwin")
+        && matches!(arch, "x86" | "x86_64" | "aarch64" | "arm" | "riscv64")
+}
+
+/// Enables the cfgs for the capabilities of the target, and reports them to the build scripts of
+/// dependents as `DEP_PSM_0_1_ASM`, `DEP_PSM_0_1_SWITCHABLE_STACK`,
+/// `DEP_PSM_0_1_UNWINDABLE_STACK`, `DEP_PSM_0_1_SWITCHABLE_CONTEXT`,
+/// `DEP_PSM_0_1_FRAME_INFORMATION` (each either `0` or `1`) and `DEP_PSM_0_1_STACK_DIRECTION`.
+fn set_capabilities(
+    asm: bool,
+    switchable_stack: bool,
+    unwindable_stack: bool,
+    switchable_context: bool,
+    frame_information: bool,
+) {
+    for (name, enabled) in [
+        ("asm", asm),
+        ("switchable_stack", switchable_stack),
+        ("unwindable_stack", unwindable_stack),
+        ("switchable_context", switchable_context),
+        ("frame_information", frame_information),
+    ] {
+        if enabled {
+            println!("cargo:rustc-cfg={}", name);
+        }
+        println!("cargo:{}={}", name, enabled as u8);
+    }
+    if asm {
+        // The stack grows downwards on all targets with assembly.
+        println!("cargo:stack_direction=descending");
+    }
+}
+
+/// Whether the code is built with frame pointers, either because of `-C force-frame-pointers` or
+/// because the target keeps them by default. Only tests use this, to be ignored without them. It is
+/// reported to dependents as `DEP_PSM_0_1_FRAME_POINTERS`, either `0` or `1`, for their tests.
+fn has_frame_pointers(vendor: &str) -> bool {
+    let flags = std::env::var("CARGO_ENCODED_RUSTFLAGS").unwrap_or_default();
+    let mut forced = None;
+    let mut flags = flags.split('\x1f');
+    while let Some(flag) = flags.next() {
+        let option = match flag.strip_prefix("-C") {
+            Some("") => flags.next().unwrap_or_default(),
+            Some(option) => option,
+            None => continue,
+        };
+        if let Some(value) = option.strip_prefix("force-frame-pointers") {
+            forced = Some(!matches!(value, "=no" | "=n" | "=off" | "=false"));
+        }
+    }
+    forced.unwrap_or(vendor == "apple")
+}
+
+/// Whether the environment variable is set to anything other than an empty string or `0`.
+fn env_flag(name: &str) -> bool {
+    println!("cargo:rerun-if-env-changed={}", name);
+    std::env::var(name).is_ok_and(|value| !value.is_empty() && value != "0")
+}
+
 fn main() {
     use std::env::var;
 
-    println!("cargo:rustc-check-cfg=cfg(switchable_stack,asm,link_asm)");
+    println!(
+        "cargo:rustc-check-cfg=cfg(switchable_stack,unwindable_stack,switchable_context,asm,link_asm,naked,versioned_symbols,frame_information,frame_pointers)"
+    );
+
+    // The exported assembly routines have the version appended to their names, so that multiple
+    // versions of this crate can be linked together.
+    let version: String = var("CARGO_PKG_VERSION")
+        .unwrap()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    println!("cargo:rustc-env=PSM_SYMBOL_SUFFIX=_v{}", version);
+    // The `links` key names the `DEP_PSM_0_1_*` variables, and has to be changed along with
+    // semver-incompatible releases.
+    let major = var("CARGO_PKG_VERSION_MAJOR").unwrap();
+    let compatible = if major == "0" {
+        format!("0_{}", var("CARGO_PKG_VERSION_MINOR").unwrap())
+    } else {
+        major
+    };
+    assert_eq!(
+        var("CARGO_MANIFEST_LINKS").unwrap(),
+        format!("psm_{}", compatible),
+        "the `links` key in Cargo.toml does not match the version"
+    );
+
+    // Remove the archive of a previous build, which would otherwise be left behind if the assembly
+    // is no longer built, and be inspected by `tests/gnu_property_note.rs`.
+    let out_dir = var("OUT_DIR").unwrap();
+    let _ = std::fs::remove_file(std::path::Path::new(&out_dir).join("libpsm_s.a"));
+
+    let frame_pointers = has_frame_pointers(&var("CARGO_CFG_TARGET_VENDOR").unwrap());
+    if frame_pointers {
+        println!("cargo:rustc-cfg=frame_pointers");
+    }
+    println!("cargo:frame_pointers={}", frame_pointers as u8);
 
     if var("CARGO_CFG_MIRI").is_ok() {
-        // Miri doesn't have a stack limit and the inline asm wouldn't work on miri anyway.
+        // Neither the assembly nor inline asm work under Miri, but `on_stack`, `on_stack_unwind`
+        // (other than on Windows), `replace_stack` and the stack information are emulated by
+        // `src/miri.rs`. Context switching and the frame information are not.
+        let windows = var("CARGO_CFG_TARGET_OS").unwrap() == "windows";
+        set_capabilities(true, true, !windows, false, false);
         return;
     }
 
@@ -71,6 +202,34 @@ fn main() {
     let os = var("CARGO_CFG_TARGET_OS").unwrap();
     let endian = var("CARGO_CFG_TARGET_ENDIAN").unwrap();
 
+    println!("cargo:rerun-if-changed=src/arch");
+
+    if var("CARGO_FEATURE_NO_ASM").is_ok() || env_flag("PSM_NO_ASM") {
+        println!(
+            "cargo:warning=psm: assembly is disabled by the `no-asm` feature or `PSM_NO_ASM`, \
+             cfgs asm, switchable_stack, unwindable_stack, switchable_context and frame_information \
+             are not set"
+        );
+        set_capabilities(false, false, false, false, false);
+        return;
+    }
+
+    println!("cargo:rerun-if-env-changed=PSM_ASM_FILE");
+    let forced_asm = var("PSM_ASM_FILE").ok().filter(|file| !file.is_empty());
+
+    let naked = forced_asm.is_none() && has_naked_functions(&arch, &os);
+    if naked && !env_flag("PSM_NO_NAKED") {
+        println!("cargo:rustc-cfg=naked");
+        set_capabilities(
+            true,
+            true,
+            has_unwind_information(&arch, &os),
+            has_context_switch(&arch, &os),
+            has_frame_information(&arch, &os),
+        );
+        return;
+    }
+
     let mut cfg = cc::Build::new();
 
     let msvc = cfg.get_compiler().is_like_msvc();
@@ -83,18 +242,47 @@ fn main() {
     // supports compiling MASM, but that is not stable yet
     let masm = msvc && var("HOST").expect("HOST env not set").contains("windows");
 
-    let asm = if let Some((asm, canswitch)) = find_assembly(&arch, &endian, &os, &env, masm) {
-        println!("cargo:rustc-cfg=asm");
+    let asm = if let Some(asm) = forced_asm {
+        // Stack switching is supported by the assembly for all targets other than Windows, while
+        // context switching and the frame information are only implemented by some of the files.
+        println!("cargo:rerun-if-changed={}", asm);
+        let canswitch = !matches!(&*os, "windows" | "cygwin");
+        let unwind = canswitch && has_unwind_information(&arch, &os);
+        let context = canswitch
+            && std::fs::read_to_string(&asm).is_ok_and(|s| s.contains("rust_psm_swap_context"));
+        let frame_info = has_frame_information(&arch, &os)
+            && std::fs::read_to_string(&asm).is_ok_and(|s| s.contains("rust_psm_frame_pointer"));
+        println!(
+            "cargo:warning=psm: using {} from `PSM_ASM_FILE`, cfgs asm{}{}{}{} are set",
+            asm,
+            if canswitch { ", switchable_stack" } else { "" },
+            if unwind { ", unwindable_stack" } else { "" },
+            if context { ", switchable_context" } else { "" },
+            if frame_info {
+                ", frame_information"
+            } else {
+                ""
+            },
+        );
         println!("cargo:rustc-cfg=link_asm");
-        if canswitch {
-            println!("cargo:rustc-cfg=switchable_stack")
-        }
+        set_capabilities(true, canswitch, unwind, context, frame_info);
         asm
+    } else if let Some((asm, canswitch)) = find_assembly(&arch, &endian, &os, &env, masm) {
+        println!("cargo:rustc-cfg=link_asm");
+        set_capabilities(
+            true,
+            canswitch,
+            canswitch && has_unwind_information(&arch, &os),
+            canswitch && has_context_switch(&arch, &os),
+            has_frame_information(&arch, &os),
+        );
+        asm.to_string()
     } else {
         println!(
             "cargo:warning=Target {}-{}-{} has no assembly files!",
             arch, os, env
         );
+        set_capabilities(false, false, false, false, false);
         return;
     };
 
@@ -103,6 +291,19 @@ fn main() {
         cfg.define(&*format!("CFG_TARGET_OS_{}", os), None);
         cfg.define(&*format!("CFG_TARGET_ARCH_{}", arch), None);
         cfg.define(&*format!("CFG_TARGET_ENV_{}", env), None);
+        // Lets assembly files given in `PSM_ASM_FILE` include `psm.h`.
+        cfg.include("src/arch");
+        // MIPS release 6 changed the encodings of some instructions, such as `jr`, so make sure the
+        // assembler uses the new ones even if it defaults to an older revision.
+        if arch == "mips32r6" || arch == "mips64r6" {
+            cfg.flag(&*format!("-march={}", arch));
+        }
+        // The names are versioned by `psm.h`, which needs the preprocessor. When targeting MSVC
+        // and in the prebuilt object the plain names are used.
+        if !asm.ends_with(".o") {
+            cfg.define("PSM_VERSION", version.as_str());
+            println!("cargo:rustc-cfg=versioned_symbols");
+        }
     }
 
     // For wasm targets we ship a precompiled `*.o` file so we just pass that
@@ -119,8 +320,8 @@ fn main() {
         let out_dir = std::env::var("OUT_DIR").expect("OUT_DIR environment variable not set");
         let output_path = PathBuf::from(&out_dir).join("libpsm_s.a");
 
-        let object_data = read(asm).expect("Failed to read object file");
-        let file_metadata = metadata(asm).expect("Failed to read file metadata");
+        let object_data = read(&asm).expect("Failed to read object file");
+        let file_metadata = metadata(&asm).expect("Failed to read file metadata");
 
         // Extract file metadata
         let mtime = file_metadata
@@ -143,7 +344,7 @@ fn main() {
         #[cfg(not(unix))]
         let (uid, gid, perms) = (0, 0, 0o644);
 
-        let filename = asm.rsplit('/').next().unwrap_or(asm);
+        let filename = asm.rsplit('/').next().unwrap_or(&asm);
         let member = NewArchiveMember {
             buf: Box::new(object_data),
             object_reader:
@@ -184,7 +385,7 @@ fn main() {
         println!("cargo:rustc-link-search=native={}", out_dir);
         println!("cargo:rustc-link-lib=static=psm_s");
     } else {
-        cfg.file(asm);
+        cfg.file(&asm);
         cfg.compile("libpsm_s.a");
     }
 }
@@ -1,7 +1,9 @@
 extern crate psm;
 
+#[cfg(switchable_stack)]
 use std::panic;
 
+#[cfg(switchable_stack)]
 const CHAIN_DEPTH: usize = 16;
 
 psm::psm_stack_manipulation! {
@@ -1,13 +1,14 @@
 #include "psm.h"
 #include "gnu_stack_note.s"
+#include "gnu_property_note.s"
 
 .text
 
 #if defined(CFG_TARGET_OS_darwin) || defined(CFG_TARGET_OS_macos) || defined(CFG_TARGET_OS_ios) || defined(CFG_TARGET_OS_tvos)
 
-#define GLOBL(fnname) .globl _##fnname
+#define GLOBL(fnname) .globl PSM_UNDERSCORE(fnname)
 #define TYPE(fnname)
-#define FUNCTION(fnname) _##fnname
+#define FUNCTION(fnname) PSM_UNDERSCORE(fnname)
 #define END_FUNCTION(fnname)
 
 #elif defined(CFG_TARGET_OS_windows)
@@ -26,6 +27,24 @@
 
 #endif
 
+/*
+    Branch target identification and return address signing, which are only used on ELF targets.
+    The instructions are spelled as the hints they are encoded as, so that older assemblers accept
+    them, and are no-ops on processors without these features.
+
+    Every function starts with a `bti c` landing pad, or with `paciasp`, which is one too.
+    `rust_psm_context_start` is only ever returned to.
+*/
+#if defined(__ELF__)
+#define BTI_C hint #34                             /* bti c */
+#define PACIASP hint #25 ; .cfi_negate_ra_state    /* paciasp */
+#define AUTIASP hint #29 ; .cfi_negate_ra_state    /* autiasp */
+#else
+#define BTI_C
+#define PACIASP
+#define AUTIASP
+#endif
+
 
 GLOBL(rust_psm_stack_direction)
 .p2align 2
@@ -33,6 +52,7 @@ TYPE(rust_psm_stack_direction)
 FUNCTION(rust_psm_stack_direction):
 /* extern "C" fn() -> u8 */
 .cfi_startproc
+    BTI_C
     orr w0, wzr, #STACK_DIRECTION_DESCENDING
     ret
 END_FUNCTION(rust_psm_stack_direction)
@@ -45,21 +65,56 @@ TYPE(rust_psm_stack_pointer)
 FUNCTION(rust_psm_stack_pointer):
 /* extern "C" fn() -> *mut u8 */
 .cfi_startproc
+    BTI_C
     mov x0, sp
     ret
 END_FUNCTION(rust_psm_stack_pointer)
 .cfi_endproc
 
 
+GLOBL(rust_psm_frame_pointer)
+.p2align 2
+TYPE(rust_psm_frame_pointer)
+FUNCTION(rust_psm_frame_pointer):
+/* extern "C" fn() -> *mut u8 */
+.cfi_startproc
+    BTI_C
+    mov x0, x29
+    ret
+END_FUNCTION(rust_psm_frame_pointer)
+.cfi_endproc
+
+
+GLOBL(rust_psm_return_address)
+.p2align 2
+TYPE(rust_psm_return_address)
+FUNCTION(rust_psm_return_address):
+/* extern "C" fn() -> *const u8 */
+.cfi_startproc
+    BTI_C
+    ldr x0, [x29, #8]
+    ret
+END_FUNCTION(rust_psm_return_address)
+.cfi_endproc
+
+
 GLOBL(rust_psm_replace_stack)
 .p2align 2
 TYPE(rust_psm_replace_stack)
 FUNCTION(rust_psm_replace_stack):
 /* extern "C" fn(r0: usize, r1: extern "C" fn(usize), r2: *mut u8) */
 .cfi_startproc
-/* All we gotta do is set the stack pointer to %rdx & tail-call the callback in %rsi */
+    BTI_C
+/*
+    All we gotta do is set the stack pointer to x2 & call the callback in x1.
+
+    The callback is called rather than tail-called, see psm.h.
+*/
+    .cfi_undefined x30
     mov sp, x2
-    br x1
+    mov x29, xzr
+    blr x1
+    brk #1
 END_FUNCTION(rust_psm_replace_stack)
 .cfi_endproc
 
@@ -70,20 +125,130 @@ TYPE(rust_psm_on_stack)
 FUNCTION(rust_psm_on_stack):
 /* extern "C" fn(r0: usize, r1: usize, r2: extern "C" fn(usize, usize), r3: *mut u8) */
 .cfi_startproc
-    stp x29, x30, [sp, #-16]!
-    .cfi_def_cfa sp, 16
-    mov x29, sp
-    .cfi_def_cfa x29, 16
-    .cfi_offset x29, -16
-    .cfi_offset x30, -8
+/*
+    The old stack pointer is kept in x19, while x29 points to a copy of the caller's frame record,
+    see psm.h.
+
+    The link register is signed before it is spilled, and authenticated once it is reloaded, with
+    the same stack pointer as the modifier.
+*/
+    PACIASP
+    stp x29, x30, [sp, #-32]!
+    .cfi_def_cfa sp, 32
+    .cfi_offset x29, -32
+    .cfi_offset x30, -24
+    str x19, [sp, #16]
+    .cfi_offset x19, -16
+    mov x19, sp
+    .cfi_def_cfa x19, 32
+    stp x29, x30, [x3, #-16]!
+    mov x29, x3
     mov sp, x3
     blr x2
-    mov sp, x29
-    .cfi_def_cfa sp, 16
-    ldp x29, x30, [sp], #16
+    mov sp, x19
+    .cfi_def_cfa sp, 32
+    ldr x19, [sp, #16]
+    .cfi_restore x19
+    ldp x29, x30, [sp], #32
     .cfi_def_cfa sp, 0
     .cfi_restore x29
     .cfi_restore x30
+    AUTIASP
     ret
 END_FUNCTION(rust_psm_on_stack)
 .cfi_endproc
+
+
+GLOBL(rust_psm_swap_context)
+.p2align 2
+TYPE(rust_psm_swap_context)
+FUNCTION(rust_psm_swap_context):
+/* extern "C" fn(x0: *mut Context, x1: *const Context) */
+.cfi_startproc
+    BTI_C
+/*
+    Store the callee-saved registers onto the current stack, save the stack pointer into `from` and
+    load the same set of registers from the stack saved in `to`.
+*/
+    sub sp, sp, #160
+    .cfi_def_cfa_offset 160
+    stp x19, x20, [sp, #0]
+    stp x21, x22, [sp, #16]
+    stp x23, x24, [sp, #32]
+    stp x25, x26, [sp, #48]
+    stp x27, x28, [sp, #64]
+    stp x29, x30, [sp, #80]
+    stp d8, d9, [sp, #96]
+    stp d10, d11, [sp, #112]
+    stp d12, d13, [sp, #128]
+    stp d14, d15, [sp, #144]
+    .cfi_offset x19, -160
+    .cfi_offset x20, -152
+    .cfi_offset x21, -144
+    .cfi_offset x22, -136
+    .cfi_offset x23, -128
+    .cfi_offset x24, -120
+    .cfi_offset x25, -112
+    .cfi_offset x26, -104
+    .cfi_offset x27, -96
+    .cfi_offset x28, -88
+    .cfi_offset x29, -80
+    .cfi_offset x30, -72
+    mov x9, sp
+    str x9, [x0]
+    ldr x9, [x1]
+    mov sp, x9
+    ldp x19, x20, [sp, #0]
+    ldp x21, x22, [sp, #16]
+    ldp x23, x24, [sp, #32]
+    ldp x25, x26, [sp, #48]
+    ldp x27, x28, [sp, #64]
+    ldp x29, x30, [sp, #80]
+    ldp d8, d9, [sp, #96]
+    ldp d10, d11, [sp, #112]
+    ldp d12, d13, [sp, #128]
+    ldp d14, d15, [sp, #144]
+    add sp, sp, #160
+    .cfi_def_cfa_offset 0
+    ret
+END_FUNCTION(rust_psm_swap_context)
+.cfi_endproc
+
+
+GLOBL(rust_psm_init_context)
+.p2align 2
+TYPE(rust_psm_init_context)
+FUNCTION(rust_psm_init_context):
+/* extern "C" fn(x0: *mut u8, x1: extern "C" fn(usize) -> !, x2: usize) -> *mut u8 */
+.cfi_startproc
+    BTI_C
+/*
+    Lay out a frame below x0 that looks as if `rust_psm_swap_context` had been called from
+    `rust_psm_context_start`, with the argument in x19 and the callback in x20.
+*/
+    sub x0, x0, #160
+    stp x2, x1, [x0, #0]
+    stp xzr, xzr, [x0, #16]
+    stp xzr, xzr, [x0, #32]
+    stp xzr, xzr, [x0, #48]
+    stp xzr, xzr, [x0, #64]
+    adr x9, rust_psm_context_start
+    stp xzr, x9, [x0, #80]
+    stp xzr, xzr, [x0, #96]
+    stp xzr, xzr, [x0, #112]
+    stp xzr, xzr, [x0, #128]
+    stp xzr, xzr, [x0, #144]
+    ret
+END_FUNCTION(rust_psm_init_context)
+.cfi_endproc
+
+
+.p2align 2
+rust_psm_context_start:
+/* Not a real function: the first `rust_psm_swap_context` to a new context returns here. */
+.cfi_startproc
+.cfi_undefined x30
+    mov x0, x19
+    blr x20
+    brk #1
+.cfi_endproc
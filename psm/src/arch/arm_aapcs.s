@@ -6,14 +6,16 @@
 
 #if defined(CFG_TARGET_OS_darwin) || defined(CFG_TARGET_OS_macos) || defined(CFG_TARGET_OS_ios) || defined(CFG_TARGET_OS_tvos)
 
-#define GLOBL(fnname) .globl _##fnname
-#define THUMBTYPE(fnname) .thumb_func _##fnname
-#define FUNCTION(fnname) _##fnname
+#define GLOBL(fnname) .globl PSM_UNDERSCORE(fnname)
+#define THUMBTYPE(fnname) .thumb_func PSM_UNDERSCORE(fnname)
+#define FUNCTION(fnname) PSM_UNDERSCORE(fnname)
 #define THUMBFN .code 16
 #define SIZE(fnname,endlabel)
 #define FNSTART
 #define CANTUNWIND
 #define FNEND
+#define SAVE(...)
+#define SETFP(fpreg,spreg)
 
 #else
 
@@ -25,6 +27,8 @@
 #define FNSTART .fnstart
 #define CANTUNWIND .cantunwind
 #define FNEND .fnend
+#define SAVE(...) .save __VA_ARGS__
+#define SETFP(fpreg,spreg) .setfp fpreg, spreg
 
 #endif
 
@@ -71,9 +75,15 @@ FUNCTION(rust_psm_replace_stack):
 /* extern "C" fn(r0: usize, r1: extern "C" fn(usize), r2: *mut u8) */
 FNSTART
 .cfi_startproc
-/* All we gotta do is set the stack pointer to %rdx & tail-call the callback in %rsi */
+/*
+    All we gotta do is set the stack pointer to r2 & call the callback in r1.
+    The callback is called rather than tail-called, see psm.h. The function is also `.cantunwind`
+    in the ARM unwind tables.
+*/
+    .cfi_undefined lr
     mov sp, r2
-    bx r1
+    blx r1
+    udf #0
 .rust_psm_replace_stack_end:
 SIZE(rust_psm_replace_stack,.rust_psm_replace_stack_end)
 .cfi_endproc
@@ -90,8 +100,10 @@ FUNCTION(rust_psm_on_stack):
 FNSTART
 .cfi_startproc
     push {r4, lr}
+    SAVE({r4, lr})
     .cfi_def_cfa_offset 8
     mov r4, sp
+    SETFP(r4, sp)
     .cfi_def_cfa_register r4
     .cfi_offset lr, -4
     .cfi_offset r4, -8
@@ -103,5 +115,5 @@ FNSTART
 .rust_psm_on_stack_end:
 SIZE(rust_psm_on_stack,.rust_psm_on_stack_end)
 .cfi_endproc
-CANTUNWIND
+/* The unwind table entry lets panics unwind out of the callback through this frame. */
 FNEND
@@ -0,0 +1,32 @@
+/*
+    Marks the object as compatible with the control-flow protection features of the target, which
+    the linker only enables for the output if every input object is marked. The note has the layout
+    of `NT_GNU_PROPERTY_TYPE_0` with a single property.
+*/
+#if defined(__ELF__) && (defined(__x86_64__) || defined(__aarch64__))
+
+#if defined(__x86_64__)
+/*
+    Shadow stacks are not advertised: `rust_psm_replace_stack` and `rust_psm_swap_context` move to
+    other stacks without the shadow stack following them, so the first `ret` there would fault.
+*/
+#define PSM_PROPERTY_TYPE 0xc0000002   /* GNU_PROPERTY_X86_FEATURE_1_AND */
+#define PSM_PROPERTY_FEATURES 1        /* GNU_PROPERTY_X86_FEATURE_1_IBT */
+#else
+#define PSM_PROPERTY_TYPE 0xc0000000   /* GNU_PROPERTY_AARCH64_FEATURE_1_AND */
+#define PSM_PROPERTY_FEATURES 3        /* GNU_PROPERTY_AARCH64_FEATURE_1_BTI | _PAC */
+#endif
+
+.pushsection .note.gnu.property,"a",%note
+.p2align 3
+.long 4            /* n_namesz */
+.long 16           /* n_descsz */
+.long 5            /* n_type: NT_GNU_PROPERTY_TYPE_0 */
+.asciz "GNU"
+.long PSM_PROPERTY_TYPE
+.long 4            /* pr_datasz */
+.long PSM_PROPERTY_FEATURES
+.p2align 3
+.popsection
+
+#endif
@@ -28,14 +28,44 @@ rust_psm_stack_pointer:
 .cfi_endproc
 
 
+.globl rust_psm_frame_pointer
+.align 2
+.type rust_psm_frame_pointer,@function
+rust_psm_frame_pointer:
+/* extern "C" fn() -> *mut u8 */
+.cfi_startproc
+    move $r4, $r22
+    jr $r1
+.rust_psm_frame_pointer_end:
+.size       rust_psm_frame_pointer,.rust_psm_frame_pointer_end-rust_psm_frame_pointer
+.cfi_endproc
+
+
+.globl rust_psm_return_address
+.align 2
+.type rust_psm_return_address,@function
+rust_psm_return_address:
+/* extern "C" fn() -> *const u8 */
+.cfi_startproc
+    ld.d $r4, $r22, -8
+    jr $r1
+.rust_psm_return_address_end:
+.size       rust_psm_return_address,.rust_psm_return_address_end-rust_psm_return_address
+.cfi_endproc
+
+
 .globl rust_psm_replace_stack
 .align 2
 .type rust_psm_replace_stack,@function
 rust_psm_replace_stack:
 /* extern "C" fn(r4: usize, r5: extern "C" fn(usize), r6: *mut u8) */
 .cfi_startproc
+/* The callback is called rather than tail-called, see psm.h. */
+    .cfi_undefined 1
     move $r3, $r6
-    jr $r5
+    move $r22, $r0
+    jirl $r1, $r5, 0
+    break 0
 .rust_psm_replace_stack_end:
 .size       rust_psm_replace_stack,.rust_psm_replace_stack_end-rust_psm_replace_stack
 .cfi_endproc
@@ -47,17 +77,26 @@ rust_psm_replace_stack:
 rust_psm_on_stack:
 /* extern "C" fn(r4: usize, r5: usize, r6: extern "C" fn(usize, usize), r7: *mut u8) */
 .cfi_startproc
+/*
+    $r22 points to a copy of the caller's frame record at the top of the new stack, see psm.h. The
+    old stack pointer is saved just below it.
+*/
     st.d $r1, $r7, -8
-    st.d $r3, $r7, -16
-    addi.d $r3, $r7, -16
-    .cfi_def_cfa 3, 16
+    st.d $r22, $r7, -16
+    st.d $r3, $r7, -24
+    addi.d $r22, $r7, 0
+    .cfi_def_cfa 22, 0
     .cfi_offset 1, -8
-    .cfi_offset 3, -16
+    .cfi_offset 22, -16
+    .cfi_offset 3, -24
+    addi.d $r3, $r7, -32
     jirl $r1, $r6, 0
-    ld.d $r1, $r3, 8
+    ld.d $r1, $r22, -8
     .cfi_restore 1
-    ld.d $r3, $r3, 0
-    .cfi_restore 3
+    ld.d $r3, $r22, -24
+    .cfi_def_cfa 3, 0
+    ld.d $r22, $r22, -16
+    .cfi_restore 22
     jr $r1
 .rust_psm_on_stack_end:
 .size       rust_psm_on_stack,.rust_psm_on_stack_end-rust_psm_on_stack
@@ -5,6 +5,10 @@ used where.
 This is an "EABI" implementation based on the following page:
 
 http://www.cygwin.com/ml/binutils/2003-06/msg00436.html
+
+The same code is used for the release 6 (mips64r6) targets, so it only uses instructions that
+release 6 kept. The build script makes the assembler use the release 6 encodings for them, which
+differ for some instructions, such as `jr`.
 */
 
 #include "psm.h"
@@ -51,12 +55,16 @@ rust_psm_stack_pointer:
 /* extern "C" fn(r4: usize, r5: extern "C" fn(usize), r6: *mut u8) */
 rust_psm_replace_stack:
 .cfi_startproc
+/* The callback is called rather than tail-called, see psm.h. */
+    .cfi_undefined 31
     move $25, $5
-    jr $5
+    move $30, $0
+    jalr $31, $5
     move $29, $6
+    break
 .end rust_psm_replace_stack
 .rust_psm_replace_stack_end:
-.size       rust_psm_replace_stack,.rust_psm_on_stack_end-rust_psm_on_stack
+.size       rust_psm_replace_stack,.rust_psm_replace_stack_end-rust_psm_replace_stack
 .cfi_endproc
 
 
@@ -69,13 +77,14 @@ rust_psm_on_stack:
 .cfi_startproc
     sd $29, -8($7)
     sd $31, -16($7)
-    .cfi_def_cfa 7, 0
+    /* See the comment in mips_eabi.s on why the stack is not switched in the delay slot. */
+    daddiu $29, $7, -16
+    .cfi_def_cfa 29, 16
     .cfi_offset 31, -16
     .cfi_offset 29, -8
     move $25, $6
     jalr $31, $6
-    daddiu $29, $7, -16
-    .cfi_def_cfa 29, 16
+    nop
     ld $31, 0($29)
     .cfi_restore 31
     ld $29, 8($29)
@@ -38,10 +38,15 @@ rust_psm_replace_stack:
 /* extern "C" fn(3: usize, 4: extern "C" fn(usize), 5: *mut u8) */
 .cfi_startproc
 /* NOTE: perhaps add a debug-assertion for stack alignment? */
+/* The callback is called rather than tail-called, see psm.h. */
+    .cfi_undefined lr
     addi 5, 5, -16
+    li 0, 0
+    stw 0, 0(5)
     mr 1, 5
     mtctr 4
-    bctr
+    bctrl
+    trap
 .rust_psm_replace_stack_end:
 .size       rust_psm_replace_stack,.rust_psm_replace_stack_end-rust_psm_replace_stack
 .cfi_endproc
@@ -66,10 +71,8 @@ rust_psm_on_stack:
     lwz 0, 8(1)
     mtlr 0
     .cfi_restore lr
-    /* FIXME: after this instruction backtrace breaks until control returns to the caller
-       That being said compiler-generated code has the same issue, so I guess that is fine for now?
-    */
     lwz 1, 0(1)
+    .cfi_def_cfa r1, 0
     .cfi_restore r1
     blr
 .rust_psm_on_stack_end:
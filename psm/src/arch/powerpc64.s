@@ -45,10 +45,15 @@ rust_psm_replace_stack:
     ld 2, 8(4)
     ld 4, 0(4)
     /* do not allocate the whole 112-byte sized frame, we know wont be used */
+/* The callback is called rather than tail-called, see psm.h. */
+    .cfi_undefined lr
     addi 5, 5, -48
+    li 0, 0
+    std 0, 0(5)
     mr 1, 5
     mtctr 4
-    bctr
+    bctrl
+    trap
 .rust_psm_replace_stack_end:
 .size       rust_psm_replace_stack,.rust_psm_replace_stack_end-rust_psm_replace_stack
 .cfi_endproc
@@ -80,10 +85,8 @@ rust_psm_on_stack:
     ld 0, 104(1)
     mtlr 0
     .cfi_restore lr
-    /* FIXME: after this instruction backtrace breaks until control returns to the caller.
-       That being said compiler-generated code has the same issue, so I guess that is fine for now?
-    */
     ld 1, 0(1)
+    .cfi_def_cfa r1, 0
     .cfi_restore r1
     blr
 .rust_psm_on_stack_end:
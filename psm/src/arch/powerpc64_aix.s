@@ -1,3 +1,4 @@
+#include "psm.h"
 .csect .text[PR],2
 .file "powerpc64_aix.s"
 
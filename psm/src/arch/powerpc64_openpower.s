@@ -44,11 +44,16 @@ rust_psm_stack_pointer:
 rust_psm_replace_stack:
 /* extern "C" fn(3: usize, 4: extern "C" fn(usize), 5: *mut u8) */
 .cfi_startproc
+/* The callback is called rather than tail-called, see psm.h. */
+    .cfi_undefined lr
     addi 5, 5, -32
+    li 0, 0
+    std 0, 0(5)
     mtctr 4
     mr 12, 4
     mr 1, 5
-    bctr
+    bctrl
+    trap
 .rust_psm_replace_stack_end:
 .size       rust_psm_replace_stack,.rust_psm_replace_stack_end-rust_psm_replace_stack
 .cfi_endproc
@@ -79,8 +84,9 @@ rust_psm_on_stack:
     ld 0, 40(1)
     mtlr 0
     .cfi_restore lr
-    /* FIXME: after this instructin backtrace breaks until control returns to the caller */
     ld 1, 0(1)
+    .cfi_def_cfa r1, 0
+    .cfi_restore r1
     blr
 .rust_psm_on_stack_end:
 .size       rust_psm_on_stack,.rust_psm_on_stack_end-rust_psm_on_stack
@@ -8,3 +8,49 @@ Various defines for values produced by `-DCFG_TARGET_*` flags. Only needs to be
 #define darwin 1
 #define macos 2
 #define ios 3
+
+
+/*
+The exported routines are named with the version of this crate appended, which the build script
+passes in as `PSM_VERSION`, so that several versions of psm can be linked into the same binary.
+The names are defined as macros expanding to the versioned names, so that the assembly files can
+refer to the routines by their plain names. Those pasting the name into another token must use
+`PSM_UNDERSCORE` instead of `_##name`, as arguments of `##` are not macro-expanded.
+*/
+#ifdef PSM_VERSION
+#define PSM_SYMBOL_PASTE(name, version) name##_v##version
+#define PSM_SYMBOL_EXPAND(name, version) PSM_SYMBOL_PASTE(name, version)
+#define PSM_SYMBOL(name) PSM_SYMBOL_EXPAND(name, PSM_VERSION)
+#else
+#define PSM_SYMBOL(name) name
+#endif
+
+#define PSM_UNDERSCORE_PASTE(name) _##name
+#define PSM_UNDERSCORE(name) PSM_UNDERSCORE_PASTE(name)
+
+#define rust_psm_stack_direction PSM_SYMBOL(rust_psm_stack_direction)
+#define rust_psm_stack_pointer PSM_SYMBOL(rust_psm_stack_pointer)
+#define rust_psm_frame_pointer PSM_SYMBOL(rust_psm_frame_pointer)
+#define rust_psm_return_address PSM_SYMBOL(rust_psm_return_address)
+#define rust_psm_replace_stack PSM_SYMBOL(rust_psm_replace_stack)
+#define rust_psm_on_stack PSM_SYMBOL(rust_psm_on_stack)
+#define rust_psm_swap_context PSM_SYMBOL(rust_psm_swap_context)
+#define rust_psm_init_context PSM_SYMBOL(rust_psm_init_context)
+
+
+/*
+Conventions shared by the routines, in the assembly files as well as in the naked functions of
+`src/naked`:
+
+* `rust_psm_replace_stack` calls the callback rather than tail-calling it, so that its return
+  address points back into the routine, which marks the end of the stack for unwinders by leaving
+  the return address undefined. The frame pointer (or back chain) is cleared for the same reason.
+
+* `rust_psm_on_stack` points the frame pointer to a synthetic frame record at the top of the new
+  stack. The record is a copy of the caller's, so that profilers walking the frame pointer chain
+  continue from the new stack into the caller's frames.
+
+* `rust_psm_swap_context` saves the callee-saved registers onto the current stack and restores them
+  from the stack it switches to. Both stacks have the same frame layout at the point of the switch,
+  so the CFI describes either of them.
+*/
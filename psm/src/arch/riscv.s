@@ -28,14 +28,44 @@ rust_psm_stack_pointer:
 .cfi_endproc
 
 
+.globl rust_psm_frame_pointer
+.p2align 2
+.type rust_psm_frame_pointer,@function
+rust_psm_frame_pointer:
+/* extern "C" fn() -> *mut u8 */
+.cfi_startproc
+    add x10, x8, x0
+    jr x1
+.rust_psm_frame_pointer_end:
+.size       rust_psm_frame_pointer,.rust_psm_frame_pointer_end-rust_psm_frame_pointer
+.cfi_endproc
+
+
+.globl rust_psm_return_address
+.p2align 2
+.type rust_psm_return_address,@function
+rust_psm_return_address:
+/* extern "C" fn() -> *const u8 */
+.cfi_startproc
+    lw x10, -4(x8)
+    jr x1
+.rust_psm_return_address_end:
+.size       rust_psm_return_address,.rust_psm_return_address_end-rust_psm_return_address
+.cfi_endproc
+
+
 .globl rust_psm_replace_stack
 .p2align 2
 .type rust_psm_replace_stack,@function
 rust_psm_replace_stack:
 /* extern "C" fn(x10: usize, x11: extern "C" fn(usize), x12: *mut u8) */
 .cfi_startproc
+/* The callback is called rather than tail-called, see psm.h. */
+    .cfi_undefined x1
     add x2, x12, x0
-    jr x11
+    add x8, x0, x0
+    jalr x1, x11, 0
+    unimp
 .rust_psm_replace_stack_end:
 .size       rust_psm_replace_stack,.rust_psm_replace_stack_end-rust_psm_replace_stack
 .cfi_endproc
@@ -47,17 +77,26 @@ rust_psm_replace_stack:
 rust_psm_on_stack:
 /* extern "C" fn(x10: usize, x11: usize, x12: extern "C" fn(usize, usize), x13: *mut u8) */
 .cfi_startproc
-    sw x1, -12(x13)
-    sw x2, -16(x13)
+/*
+    x8 points to a copy of the caller's frame record at the top of the new stack, see psm.h. The old
+    stack pointer is saved just below it.
+*/
+    sw x1, -4(x13)
+    sw x8, -8(x13)
+    sw x2, -12(x13)
+    addi x8, x13, 0
+    .cfi_def_cfa x8, 0
+    .cfi_offset x1, -4
+    .cfi_offset x8, -8
+    .cfi_offset x2, -12
     addi x2, x13, -16
-    .cfi_def_cfa x2, 16
-    .cfi_offset x1, -12
-    .cfi_offset x2, -16
     jalr x1, x12, 0
-    lw x1, 4(x2)
+    lw x1, -4(x8)
     .cfi_restore x1
-    lw x2, 0(x2)
-    .cfi_restore x2
+    lw x2, -12(x8)
+    .cfi_def_cfa x2, 0
+    lw x8, -8(x8)
+    .cfi_restore x8
     jr x1
 .rust_psm_on_stack_end:
 .size       rust_psm_on_stack,.rust_psm_on_stack_end-rust_psm_on_stack
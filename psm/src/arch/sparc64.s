@@ -36,9 +36,12 @@ rust_psm_replace_stack:
 .cfi_startproc
     .cfi_def_cfa 0, 0
     .cfi_return_column 0
-    jmpl %o1, %g0
+    /* The callback is called rather than tail-called, see psm.h. The invalid instruction after
+       the delay slot keeps the return address within this function. */
+    jmpl %o1, %o7
     /* WEIRD: Why is the LSB set for the %sp and %fp on SPARC?? */
     add %o2, -0x7ff, %o6
+    unimp 0
 .rust_psm_replace_stack_end:
 .size       rust_psm_replace_stack,.rust_psm_replace_stack_end-rust_psm_replace_stack
 .cfi_endproc
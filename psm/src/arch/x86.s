@@ -6,9 +6,9 @@
 
 #if defined(CFG_TARGET_OS_darwin) || defined(CFG_TARGET_OS_macos) || defined(CFG_TARGET_OS_ios) || defined(CFG_TARGET_OS_tvos)
 
-#define GLOBL(fnname) .globl _##fnname
+#define GLOBL(fnname) .globl PSM_UNDERSCORE(fnname)
 #define TYPE(fnname)
-#define FUNCTION(fnname) _##fnname
+#define FUNCTION(fnname) PSM_UNDERSCORE(fnname)
 #define SIZE(fnname,endlabel)
 
 #else
@@ -47,6 +47,32 @@ SIZE(rust_psm_stack_pointer,.rust_psm_stack_pointer_end)
 .cfi_endproc
 
 
+GLOBL(rust_psm_frame_pointer)
+.p2align 4
+TYPE(rust_psm_frame_pointer)
+FUNCTION(rust_psm_frame_pointer):
+/* extern "fastcall" fn() -> *mut u8 (%eax) */
+.cfi_startproc
+    movl %ebp, %eax
+    retl
+.rust_psm_frame_pointer_end:
+SIZE(rust_psm_frame_pointer,.rust_psm_frame_pointer_end)
+.cfi_endproc
+
+
+GLOBL(rust_psm_return_address)
+.p2align 4
+TYPE(rust_psm_return_address)
+FUNCTION(rust_psm_return_address):
+/* extern "fastcall" fn() -> *const u8 (%eax) */
+.cfi_startproc
+    movl 4(%ebp), %eax
+    retl
+.rust_psm_return_address_end:
+SIZE(rust_psm_return_address,.rust_psm_return_address_end)
+.cfi_endproc
+
+
 GLOBL(rust_psm_replace_stack)
 .p2align 4
 TYPE(rust_psm_replace_stack)
@@ -61,8 +87,14 @@ FUNCTION(rust_psm_replace_stack):
    way we use the `calll` instruction, however it would also be possible to to use plain `jmpl` but
    would require to adjust the stack manually, which cannot be easily done, because the stack
    pointer argument is already stored in memory.
+
+   The return address pushed by `calll` points back into this function, which marks the end of the
+   stack for unwinders by leaving the return address undefined. The frame pointer is cleared for
+   the same reason.
  */
+    .cfi_undefined %eip
     movl 4(%esp), %esp
+    xorl %ebp, %ebp
     calll *%edx
     ud2
 .rust_psm_replace_stack_end:
@@ -76,17 +108,112 @@ TYPE(rust_psm_on_stack)
 FUNCTION(rust_psm_on_stack):
 /* extern "fastcall" fn(%ecx: usize, %edx: usize, 4(%esp): extern "fastcall" fn(usize, usize), 8(%esp): *mut u8) */
 .cfi_startproc
+/*
+    The old stack pointer is kept in %ebx, while %ebp points to a copy of the caller's frame record,
+    see psm.h.
+ */
     pushl %ebp
     .cfi_def_cfa %esp, 8
     .cfi_offset %ebp, -8
+    pushl %ebx
+    .cfi_def_cfa %esp, 12
+    .cfi_offset %ebx, -12
+    movl  %esp, %ebx
+    .cfi_def_cfa_register %ebx
+    movl  16(%ebx), %esp
+    pushl 8(%ebx)
+    pushl %ebp
     movl  %esp, %ebp
-    .cfi_def_cfa_register %ebp
-    movl  12(%ebp), %esp
-    calll *8(%ebp)
-    movl  %ebp, %esp
+    subl  $8, %esp
+    calll *12(%ebx)
+    movl  %ebx, %esp
+    .cfi_def_cfa_register %esp
+    popl  %ebx
+    .cfi_def_cfa_offset 8
+    .cfi_restore %ebx
     popl  %ebp
-    .cfi_def_cfa %esp, 4
+    .cfi_def_cfa_offset 4
+    .cfi_restore %ebp
     retl  $8
 .rust_psm_on_stack_end:
 SIZE(rust_psm_on_stack,.rust_psm_on_stack_end)
 .cfi_endproc
+
+
+GLOBL(rust_psm_swap_context)
+.p2align 4
+TYPE(rust_psm_swap_context)
+FUNCTION(rust_psm_swap_context):
+/* extern "fastcall" fn(%ecx: *mut Context, %edx: *const Context) */
+.cfi_startproc
+/*
+   Push the callee-saved registers onto the current stack, save the stack pointer into `from` and
+   pop the same set of registers from the stack saved in `to`.
+ */
+    pushl %ebp
+    .cfi_def_cfa_offset 8
+    pushl %ebx
+    .cfi_def_cfa_offset 12
+    pushl %esi
+    .cfi_def_cfa_offset 16
+    pushl %edi
+    .cfi_def_cfa_offset 20
+    .cfi_offset %ebp, -8
+    .cfi_offset %ebx, -12
+    .cfi_offset %esi, -16
+    .cfi_offset %edi, -20
+    movl %esp, (%ecx)
+    movl (%edx), %esp
+    popl %edi
+    .cfi_def_cfa_offset 16
+    popl %esi
+    .cfi_def_cfa_offset 12
+    popl %ebx
+    .cfi_def_cfa_offset 8
+    popl %ebp
+    .cfi_def_cfa_offset 4
+    retl
+.rust_psm_swap_context_end:
+SIZE(rust_psm_swap_context,.rust_psm_swap_context_end)
+.cfi_endproc
+
+
+GLOBL(rust_psm_init_context)
+.p2align 4
+TYPE(rust_psm_init_context)
+FUNCTION(rust_psm_init_context):
+/* extern "fastcall" fn(%ecx: *mut u8, %edx: extern "fastcall" fn(usize) -> !, 4(%esp): usize) -> *mut u8 */
+.cfi_startproc
+/*
+   Lay out a frame below %ecx that looks as if `rust_psm_swap_context` had been called from
+   `rust_psm_context_start`, with the callback in %esi and its argument in %ebx. The frame is
+   placed so that the stack is 16-byte aligned at the point the callback is called.
+ */
+    leal -36(%ecx), %eax
+    movl $0, (%eax)       # edi
+    movl %edx, 4(%eax)    # esi
+    movl 4(%esp), %edx
+    movl %edx, 8(%eax)    # ebx
+    movl $0, 12(%eax)     # ebp
+    calll 1f
+1:
+    .cfi_adjust_cfa_offset 4
+    popl %edx
+    .cfi_adjust_cfa_offset -4
+    leal (rust_psm_context_start-1b)(%edx), %edx
+    movl %edx, 16(%eax)   # return address
+    retl $4
+.rust_psm_init_context_end:
+SIZE(rust_psm_init_context,.rust_psm_init_context_end)
+.cfi_endproc
+
+
+.p2align 4
+rust_psm_context_start:
+/* Not a real function: the first `rust_psm_swap_context` to a new context returns here. */
+.cfi_startproc
+.cfi_undefined %eip
+    movl %ebx, %ecx
+    calll *%esi
+    ud2
+.cfi_endproc
@@ -1,14 +1,20 @@
 #include "psm.h"
 #include "gnu_stack_note.s"
+#include "gnu_property_note.s"
 /* NOTE: sysv64 calling convention is used on all x86_64 targets, including Windows! */
+/*
+    Every function starts with `endbr64`, which is a no-op unless indirect branch tracking is
+    enabled, so that it can be reached through the PLT. `rust_psm_context_start` is only ever
+    returned to.
+*/
 
 .text
 
 #if defined(CFG_TARGET_OS_darwin) || defined(CFG_TARGET_OS_macos) || defined(CFG_TARGET_OS_ios) || defined(CFG_TARGET_OS_tvos)
 
-#define GLOBL(fnname) .globl _##fnname
+#define GLOBL(fnname) .globl PSM_UNDERSCORE(fnname)
 #define TYPE(fnname)
-#define FUNCTION(fnname) _##fnname
+#define FUNCTION(fnname) PSM_UNDERSCORE(fnname)
 #define END_FUNCTION(fnname)
 
 #else
@@ -27,6 +33,7 @@ TYPE(rust_psm_stack_direction)
 FUNCTION(rust_psm_stack_direction):
 /* extern "sysv64" fn() -> u8 (%al) */
 .cfi_startproc
+    endbr64
     movb $STACK_DIRECTION_DESCENDING, %al # always descending on x86_64
     retq
 END_FUNCTION(rust_psm_stack_direction)
@@ -39,6 +46,7 @@ TYPE(rust_psm_stack_pointer)
 FUNCTION(rust_psm_stack_pointer):
 /* extern "sysv64" fn() -> *mut u8 (%rax) */
 .cfi_startproc
+    endbr64
     leaq 8(%rsp), %rax
     retq
 .rust_psm_stack_pointer_end:
@@ -46,20 +54,49 @@ END_FUNCTION(rust_psm_stack_pointer)
 .cfi_endproc
 
 
+GLOBL(rust_psm_frame_pointer)
+.p2align 4
+TYPE(rust_psm_frame_pointer)
+FUNCTION(rust_psm_frame_pointer):
+/* extern "sysv64" fn() -> *mut u8 (%rax) */
+.cfi_startproc
+    endbr64
+    movq %rbp, %rax
+    retq
+END_FUNCTION(rust_psm_frame_pointer)
+.cfi_endproc
+
+
+GLOBL(rust_psm_return_address)
+.p2align 4
+TYPE(rust_psm_return_address)
+FUNCTION(rust_psm_return_address):
+/* extern "sysv64" fn() -> *const u8 (%rax) */
+.cfi_startproc
+    endbr64
+    movq 8(%rbp), %rax
+    retq
+END_FUNCTION(rust_psm_return_address)
+.cfi_endproc
+
+
 GLOBL(rust_psm_replace_stack)
 .p2align 4
 TYPE(rust_psm_replace_stack)
 FUNCTION(rust_psm_replace_stack):
 /* extern "sysv64" fn(%rdi: usize, %rsi: extern "sysv64" fn(usize), %rdx: *mut u8) */
 .cfi_startproc
+    endbr64
 /*
-    All we gotta do is set the stack pointer to %rdx & tail-call the callback in %rsi.
+    All we gotta do is set the stack pointer to %rdx & call the callback in %rsi.
 
-    8-byte offset necessary to account for the "return" pointer that would otherwise be placed onto
-    stack with a regular call
+    The callback is called rather than tail-called, see psm.h.
 */
-    leaq -8(%rdx), %rsp
-    jmpq *%rsi
+    .cfi_undefined %rip
+    movq %rdx, %rsp
+    xorl %ebp, %ebp
+    callq *%rsi
+    ud2
 .rust_psm_replace_stack_end:
 END_FUNCTION(rust_psm_replace_stack)
 .cfi_endproc
@@ -71,16 +108,129 @@ TYPE(rust_psm_on_stack)
 FUNCTION(rust_psm_on_stack):
 /* extern "sysv64" fn(%rdi: usize, %rsi: usize, %rdx: extern "sysv64" fn(usize, usize), %rcx: *mut u8) */
 .cfi_startproc
+    endbr64
+/*
+    The old stack pointer is kept in %rbx, while %rbp points to a copy of the caller's frame record,
+    see psm.h.
+*/
     pushq %rbp
     .cfi_def_cfa %rsp, 16
     .cfi_offset %rbp, -16
-    movq  %rsp, %rbp
-    .cfi_def_cfa_register %rbp
-    movq  %rcx, %rsp
-    callq *%rdx
+    pushq %rbx
+    .cfi_def_cfa %rsp, 24
+    .cfi_offset %rbx, -24
+    movq  %rsp, %rbx
+    .cfi_def_cfa_register %rbx
+    movq  16(%rbx), %rax
+    movq  %rax, -8(%rcx)
+    movq  %rbp, -16(%rcx)
+    leaq  -16(%rcx), %rbp
     movq  %rbp, %rsp
+    callq *%rdx
+    movq  %rbx, %rsp
+    .cfi_def_cfa_register %rsp
+    popq  %rbx
+    .cfi_def_cfa_offset 16
+    .cfi_restore %rbx
     popq  %rbp
-    .cfi_def_cfa %rsp, 8
+    .cfi_def_cfa_offset 8
+    .cfi_restore %rbp
     retq
 END_FUNCTION(rust_psm_on_stack)
 .cfi_endproc
+
+
+GLOBL(rust_psm_swap_context)
+.p2align 4
+TYPE(rust_psm_swap_context)
+FUNCTION(rust_psm_swap_context):
+/* extern "sysv64" fn(%rdi: *mut Context, %rsi: *const Context) */
+.cfi_startproc
+    endbr64
+/*
+    Push the callee-saved registers and the SSE/x87 control words onto the current stack, save the
+    stack pointer into `from` and pop the same set of registers from the stack saved in `to`.
+*/
+    pushq %rbp
+    .cfi_def_cfa_offset 16
+    pushq %rbx
+    .cfi_def_cfa_offset 24
+    pushq %r12
+    .cfi_def_cfa_offset 32
+    pushq %r13
+    .cfi_def_cfa_offset 40
+    pushq %r14
+    .cfi_def_cfa_offset 48
+    pushq %r15
+    .cfi_def_cfa_offset 56
+    subq  $8, %rsp
+    .cfi_def_cfa_offset 64
+    .cfi_offset %rbp, -16
+    .cfi_offset %rbx, -24
+    .cfi_offset %r12, -32
+    .cfi_offset %r13, -40
+    .cfi_offset %r14, -48
+    .cfi_offset %r15, -56
+    stmxcsr (%rsp)
+    fnstcw 4(%rsp)
+    movq  %rsp, (%rdi)
+    movq  (%rsi), %rsp
+    ldmxcsr (%rsp)
+    fldcw 4(%rsp)
+    addq  $8, %rsp
+    .cfi_def_cfa_offset 56
+    popq  %r15
+    .cfi_def_cfa_offset 48
+    popq  %r14
+    .cfi_def_cfa_offset 40
+    popq  %r13
+    .cfi_def_cfa_offset 32
+    popq  %r12
+    .cfi_def_cfa_offset 24
+    popq  %rbx
+    .cfi_def_cfa_offset 16
+    popq  %rbp
+    .cfi_def_cfa_offset 8
+    retq
+END_FUNCTION(rust_psm_swap_context)
+.cfi_endproc
+
+
+GLOBL(rust_psm_init_context)
+.p2align 4
+TYPE(rust_psm_init_context)
+FUNCTION(rust_psm_init_context):
+/* extern "sysv64" fn(%rdi: *mut u8, %rsi: extern "sysv64" fn(usize) -> !, %rdx: usize) -> *mut u8 */
+.cfi_startproc
+    endbr64
+/*
+    Lay out a frame below %rdi that looks as if `rust_psm_swap_context` had been called from
+    `rust_psm_context_start`, with the callback in %r12 and its argument in %rbx. The control words
+    are copied from the current thread.
+*/
+    leaq  -80(%rdi), %rax
+    stmxcsr (%rax)
+    fnstcw 4(%rax)
+    movq  $0, 8(%rax)     # r15
+    movq  $0, 16(%rax)    # r14
+    movq  $0, 24(%rax)    # r13
+    movq  %rsi, 32(%rax)  # r12
+    movq  %rdx, 40(%rax)  # rbx
+    movq  $0, 48(%rax)    # rbp
+    leaq  rust_psm_context_start(%rip), %rcx
+    movq  %rcx, 56(%rax)  # return address
+    movq  $0, 64(%rax)
+    retq
+END_FUNCTION(rust_psm_init_context)
+.cfi_endproc
+
+
+.p2align 4
+rust_psm_context_start:
+/* Not a real function: the first `rust_psm_swap_context` to a new context returns here. */
+.cfi_startproc
+.cfi_undefined %rip
+    movq  %rbx, %rdi
+    callq *%r12
+    ud2
+.cfi_endproc
@@ -1,3 +1,4 @@
+#include "psm.h"
 #include "gnu_stack_note.s"
 
 .text
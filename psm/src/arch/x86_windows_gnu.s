@@ -1,3 +1,4 @@
+#include "psm.h"
 /* FIXME: this works locally but not on appveyor??!? */
 /* NOTE: fastcall calling convention used on all x86 targets */
 .text
@@ -46,10 +46,17 @@ rust_psm_stack_pointer:
 rust_psm_replace_stack:
 /* extern "C" fn(r2: usize, r3: extern "C" fn(usize), r4: *mut u8) */
 .cfi_startproc
-    /* FIXME: backtrace does not terminate cleanly for some reason */
+/*
+    The callback is called rather than tail-called, see psm.h.
+
+    An invalid instruction follows the call, so that the return address is still within this
+    function.
+*/
+    .cfi_undefined %r14
     lay %r15, -160(%r4)
-    /* FIXME: this is `basr` instead of `br` purely to remove the backtrace link to the caller */
+    xc 0(8,%r15), 0(%r15)
     basr %r14, %r3
+    .word 0
 .rust_psm_replace_stack_end:
 .size       rust_psm_replace_stack,.rust_psm_replace_stack_end-rust_psm_replace_stack
 .cfi_endproc
@@ -8,10 +8,25 @@
 //! abstraction over stack manipulation, this is unlikely to be the crate you want. Instead
 //! consider one of the safe abstractions over this crate such as `stacker`. Another good place to
 //! look at is the crates.io’s reverse dependency list.
+//!
+//! Which functions are available depends on the target, see the `psm_*` macros. The `no-asm`
+//! feature removes all of the functions that need assembly on every target, so it is not additive:
+//! crates that use them without checking for them with the macros break when any crate in the
+//! dependency graph enables it. Libraries must never enable it.
 
 #![allow(unused_macros)]
 #![no_std]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+#[cfg(feature = "alloc")]
+mod stack;
+#[cfg(feature = "alloc")]
+pub use stack::Stack;
+
 macro_rules! extern_item {
     (unsafe $($toks: tt)+) => {
         unsafe extern "C" $($toks)+
@@ -53,39 +68,130 @@ macro_rules! extern_item {
     };
 }
 
+/// Like `extern_item!`, but for items that may be unwound through.
+macro_rules! extern_item_unwind {
+    (unsafe $($toks: tt)+) => {
+        unsafe extern "C-unwind" $($toks)+
+    };
+    ($($toks: tt)+) => {
+        extern "C-unwind" $($toks)+
+    };
+}
+
+#[cfg(target_arch = "x86_64")]
+macro_rules! extern_item_unwind {
+    (unsafe $($toks: tt)+) => {
+        unsafe extern "sysv64-unwind" $($toks)+
+    };
+    ($($toks: tt)+) => {
+        extern "sysv64-unwind" $($toks)+
+    };
+}
+
+#[cfg(target_arch = "x86")]
+macro_rules! extern_item_unwind {
+    (unsafe $($toks: tt)+) => {
+        unsafe extern "fastcall-unwind" $($toks)+
+    };
+    ($($toks: tt)+) => {
+        extern "fastcall-unwind" $($toks)+
+    };
+}
+
+#[cfg(target_arch = "arm")]
+macro_rules! extern_item_unwind {
+    (unsafe $($toks: tt)+) => {
+        unsafe extern "aapcs-unwind" $($toks)+
+    };
+    ($($toks: tt)+) => {
+        extern "aapcs-unwind" $($toks)+
+    };
+}
+
+// On the targets where these routines are implemented as naked functions, `naked` defines them
+// with the same signatures instead.
+#[cfg(naked)]
+mod naked;
+#[cfg(naked)]
+use naked::*;
+
+// Under Miri the routines are emulated by `miri`, again with the same signatures.
+#[cfg(miri)]
+mod miri;
+#[cfg(miri)]
+use miri::*;
+
+/// The name of an assembly routine, which has the version of this crate appended unless the
+/// assembly could not be preprocessed (see `psm.h`).
+#[cfg(versioned_symbols)]
+macro_rules! symbol_name {
+    ($name: literal) => {
+        concat!($name, env!("PSM_SYMBOL_SUFFIX"))
+    };
+}
+
+#[cfg(not(versioned_symbols))]
+macro_rules! symbol_name {
+    ($name: literal) => {
+        $name
+    };
+}
+
 // NB: this could be nicer across multiple blocks but we cannot do it because of
 // https://github.com/rust-lang/rust/issues/65847
 extern_item! { {
     #![cfg_attr(link_asm, link(name="psm_s"))]
 
-    #[cfg(asm)]
+    #[cfg(link_asm)]
+    #[link_name = symbol_name!("rust_psm_stack_direction")]
     fn rust_psm_stack_direction() -> u8;
-    #[cfg(asm)]
+    // Only used where `stack_pointer` can't be implemented with inline assembly.
+    #[cfg(all(link_asm, not(any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        target_arch = "arm",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "riscv32",
+        target_arch = "riscv64",
+        target_arch = "loongarch64",
+        target_arch = "s390x",
+    ))))]
+    #[link_name = symbol_name!("rust_psm_stack_pointer")]
     fn rust_psm_stack_pointer() -> *mut u8;
 
-    #[cfg(all(switchable_stack, not(target_os = "windows")))]
-    #[link_name="rust_psm_replace_stack"]
+    #[cfg(all(link_asm, frame_information))]
+    #[link_name = symbol_name!("rust_psm_frame_pointer")]
+    fn rust_psm_frame_pointer() -> *mut u8;
+    #[cfg(all(link_asm, frame_information))]
+    #[link_name = symbol_name!("rust_psm_return_address")]
+    fn rust_psm_return_address() -> *const u8;
+
+    #[cfg(all(link_asm, switchable_stack, not(target_os = "windows")))]
+    #[link_name = symbol_name!("rust_psm_replace_stack")]
     fn _rust_psm_replace_stack(
         data: usize,
         callback: extern_item!(unsafe fn(usize) -> !),
         sp: *mut u8
     ) -> !;
-    #[cfg(all(switchable_stack, not(target_os = "windows")))]
-    #[link_name="rust_psm_on_stack"]
+    #[cfg(all(link_asm, switchable_stack, not(target_os = "windows")))]
+    #[link_name = symbol_name!("rust_psm_on_stack")]
     fn _rust_psm_on_stack(
         data: usize,
         return_ptr: usize,
         callback: extern_item!(unsafe fn(usize, usize)),
         sp: *mut u8,
     );
-    #[cfg(all(switchable_stack, target_os = "windows"))]
+    #[cfg(all(not(miri), switchable_stack, target_os = "windows"))]
+    #[link_name = symbol_name!("rust_psm_replace_stack")]
     fn rust_psm_replace_stack(
         data: usize,
         callback: extern_item!(unsafe fn(usize) -> !),
         sp: *mut u8,
         stack_base: *mut u8
     ) -> !;
-    #[cfg(all(switchable_stack, target_os = "windows"))]
+    #[cfg(all(not(miri), switchable_stack, target_os = "windows"))]
+    #[link_name = symbol_name!("rust_psm_on_stack")]
     fn rust_psm_on_stack(
         data: usize,
         return_ptr: usize,
@@ -93,9 +199,35 @@ extern_item! { {
         sp: *mut u8,
         stack_base: *mut u8
     );
+
+    #[cfg(all(link_asm, switchable_context))]
+    #[link_name = symbol_name!("rust_psm_swap_context")]
+    fn rust_psm_swap_context(from: *mut Context, to: *const Context);
+    #[cfg(all(link_asm, switchable_context))]
+    #[link_name = symbol_name!("rust_psm_init_context")]
+    fn rust_psm_init_context(
+        sp: *mut u8,
+        callback: extern_item!(unsafe fn(usize) -> !),
+        data: usize,
+    ) -> *mut u8;
+} }
+
+// The same routine as `rust_psm_on_stack`, declared with an ABI that allows unwinding through it.
+extern_item_unwind! { {
+    #![cfg_attr(link_asm, link(name="psm_s"))]
+
+    #[cfg(all(link_asm, unwindable_stack))]
+    #[link_name = symbol_name!("rust_psm_on_stack")]
+    #[allow(clashing_extern_declarations)]
+    fn rust_psm_on_stack_unwind(
+        data: usize,
+        return_ptr: usize,
+        callback: extern_item_unwind!(unsafe fn(usize, usize)),
+        sp: *mut u8,
+    );
 } }
 
-#[cfg(all(switchable_stack, not(target_os = "windows")))]
+#[cfg(all(not(miri), switchable_stack, not(target_os = "windows")))]
 #[inline(always)]
 unsafe fn rust_psm_replace_stack(
     data: usize,
@@ -106,7 +238,7 @@ unsafe fn rust_psm_replace_stack(
     _rust_psm_replace_stack(data, callback, sp)
 }
 
-#[cfg(all(switchable_stack, not(target_os = "windows")))]
+#[cfg(all(not(miri), switchable_stack, not(target_os = "windows")))]
 #[inline(always)]
 unsafe fn rust_psm_on_stack(
     data: usize,
@@ -118,6 +250,71 @@ unsafe fn rust_psm_on_stack(
     _rust_psm_on_stack(data, return_ptr, callback, sp)
 }
 
+/// Panics if the current thread runs with an Intel CET shadow stack, which `psm::{function}` does
+/// not support.
+///
+/// A shadow stack holds a copy of every return address pushed by `call`, and a `ret` to any other
+/// address faults. Stacks replaced by `replace_stack` would leave their entries behind for good, and
+/// switching contexts returns to addresses that were pushed on another stack. `on_stack` is
+/// unaffected, as it calls and returns normally.
+#[cfg(all(
+    switchable_stack,
+    target_arch = "x86_64",
+    not(target_os = "windows"),
+    not(miri)
+))]
+fn check_shadow_stack(function: &str) {
+    let ssp: usize;
+    // `rdsspq` is a no-op unless a shadow stack is enabled, so this is zero otherwise.
+    unsafe {
+        core::arch::asm!(
+            "xor {0:e}, {0:e}",
+            "rdsspq {0}",
+            out(reg) ssp,
+            options(nomem, nostack)
+        );
+    }
+    assert!(
+        ssp == 0,
+        "psm::{}: not supported while a shadow stack is enabled",
+        function
+    );
+}
+
+#[cfg(all(
+    switchable_stack,
+    not(all(target_arch = "x86_64", not(target_os = "windows"), not(miri)))
+))]
+#[inline(always)]
+fn check_shadow_stack(_: &str) {}
+
+/// Panics if the stack does not meet the requirements of `psm::{function}`, but only when debug
+/// assertions are enabled.
+#[cfg(switchable_stack)]
+#[inline(always)]
+fn debug_check_stack(function: &str, base: *mut u8, size: usize) {
+    debug_assert!(
+        base.addr().is_multiple_of(STACK_ALIGNMENT),
+        "psm::{}: the stack base {:p} is not aligned to `STACK_ALIGNMENT` ({} bytes)",
+        function,
+        base,
+        STACK_ALIGNMENT
+    );
+    debug_assert!(
+        size <= isize::MAX as usize,
+        "psm::{}: the stack size {} overflows `isize`",
+        function,
+        size
+    );
+    debug_assert!(
+        size.is_multiple_of(STACK_ALIGNMENT),
+        "psm::{}: the stack size {} is not a multiple of `STACK_ALIGNMENT` ({} bytes)",
+        function,
+        size,
+        STACK_ALIGNMENT
+    );
+}
+
 /// Run the closure on the provided stack.
 ///
 /// Once the closure completes its execution, the original stack pointer is restored and execution
@@ -141,6 +338,9 @@ unsafe fn rust_psm_on_stack(
 /// The previous stack may not be deallocated. If an ability to deallocate the old stack is desired
 /// consider `replace_stack` instead.
 ///
+/// Miri cannot switch stacks, so under Miri the closure runs on the current stack after the
+/// alignment and bounds of the provided stack are checked.
+///
 /// # Guidelines
 ///
 /// Memory regions that are aligned to a single page (usually 4kB) are an extremely portable choice
@@ -153,13 +353,16 @@ unsafe fn rust_psm_on_stack(
 ///
 /// # Unsafety
 ///
-/// The stack `base` address must be aligned as appropriate for the target.
+/// The stack `base` address must be aligned to [`STACK_ALIGNMENT`].
 ///
-/// The stack `size` must be a multiple of stack alignment required by target.
+/// The stack `size` must be a multiple of [`STACK_ALIGNMENT`].
 ///
 /// The `size` must not overflow `isize`.
 ///
+/// These requirements are checked when debug assertions are enabled.
+///
 /// `callback` must not unwind or return control flow by any other means than directly returning.
+/// Use [`on_stack_unwind`] if the callback may unwind.
 ///
 /// # Examples
 ///
@@ -175,6 +378,7 @@ unsafe fn rust_psm_on_stack(
 ///         (psm::stack_pointer(), 4 + 4)
 ///     });
 ///     println!("4 + 4 = {} has been calculated on stack {:p}", result, stack);
+///     alloc::dealloc(new_stack, layout);
 /// }
 /// ```
 #[cfg(switchable_stack)]
@@ -190,6 +394,7 @@ pub unsafe fn on_stack<R, F: FnOnce() -> R>(base: *mut u8, size: usize, callback
             return_ptr.write((callback.read())());
         }
     }
+    debug_check_stack("on_stack", base, size);
     let sp = match StackDirection::new() {
         StackDirection::Ascending => base,
         StackDirection::Descending => base.offset(size as isize),
@@ -206,6 +411,66 @@ pub unsafe fn on_stack<R, F: FnOnce() -> R>(base: *mut u8, size: usize, callback
     return return_value.assume_init();
 }
 
+/// Run the closure on the provided stack, allowing it to unwind.
+///
+/// This is the same as [`on_stack`], except that a panic in `callback` unwinds natively across
+/// the stack switch, back into the caller of `on_stack_unwind`, instead of being undefined
+/// behaviour. The unwinder walks from the new stack back to the original one using the unwind
+/// information of the stack switching routine.
+///
+/// This is only available where that unwind information is known to be correct, see
+/// [`CAN_UNWIND`]. That is x86, x86_64, AArch64, ARM and RISC-V 64 targets other than Windows.
+///
+/// # Unsafety
+///
+/// The same requirements as for [`on_stack`] apply, except that `callback` may unwind.
+///
+/// # Examples
+///
+/// ```
+/// use std::{alloc, panic};
+/// const STACK_ALIGN: usize = 4096;
+/// const STACK_SIZE: usize = 64 * 1024;
+/// unsafe {
+///     let layout = alloc::Layout::from_size_align(STACK_SIZE, STACK_ALIGN).unwrap();
+///     let new_stack = alloc::alloc(layout);
+///     assert!(!new_stack.is_null(), "allocations must succeed!");
+///     let result = panic::catch_unwind(|| {
+///         psm::on_stack_unwind(new_stack, STACK_SIZE, || panic!("unwinding across stacks"));
+///     });
+///     assert!(result.is_err());
+///     alloc::dealloc(new_stack, layout);
+/// }
+/// ```
+#[cfg(unwindable_stack)]
+pub unsafe fn on_stack_unwind<R, F: FnOnce() -> R>(base: *mut u8, size: usize, callback: F) -> R {
+    use core::mem::MaybeUninit;
+
+    extern_item_unwind! {
+        unsafe fn with_on_stack<R, F: FnOnce() -> R>(callback_ptr: usize, return_ptr: usize) {
+            let return_ptr = (*(return_ptr as *mut MaybeUninit<R>)).as_mut_ptr();
+            let callback = (*(callback_ptr as *mut MaybeUninit<F>)).as_ptr();
+            // Safe to move out from `F`, because closure in is forgotten in `on_stack_unwind` and
+            // dropping only occurs in this callback, even if it unwinds.
+            return_ptr.write((callback.read())());
+        }
+    }
+    debug_check_stack("on_stack_unwind", base, size);
+    let sp = match StackDirection::new() {
+        StackDirection::Ascending => base,
+        StackDirection::Descending => base.offset(size as isize),
+    };
+    let mut callback: MaybeUninit<F> = MaybeUninit::new(callback);
+    let mut return_value: MaybeUninit<R> = MaybeUninit::uninit();
+    rust_psm_on_stack_unwind(
+        &mut callback as *mut MaybeUninit<F> as usize,
+        &mut return_value as *mut MaybeUninit<R> as usize,
+        with_on_stack::<R, F>,
+        sp,
+    );
+    return_value.assume_init()
+}
+
 /// Run the provided non-terminating computation on an entirely new stack.
 ///
 /// `base` address must be the low address of the stack memory region, regardless of the stack
@@ -229,6 +494,8 @@ pub unsafe fn on_stack<R, F: FnOnce() -> R>(base: *mut u8, size: usize, callback
 /// On platforms where multiple stack pointers are available, the “current” stack pointer is
 /// replaced.
 ///
+/// Like [`on_stack`], under Miri the closure runs on the current stack instead.
+///
 /// # Guidelines
 ///
 /// Memory regions that are aligned to a single page (usually 4kB) are an extremely portable choice
@@ -241,14 +508,21 @@ pub unsafe fn on_stack<R, F: FnOnce() -> R>(base: *mut u8, size: usize, callback
 ///
 /// # Unsafety
 ///
-/// The stack `base` address must be aligned as appropriate for the target.
+/// The stack `base` address must be aligned to [`STACK_ALIGNMENT`].
 ///
-/// The stack `size` must be a multiple of stack alignment required by target.
+/// The stack `size` must be a multiple of [`STACK_ALIGNMENT`].
 ///
 /// The `size` must not overflow `isize`.
 ///
+/// These requirements are checked when debug assertions are enabled.
+///
 /// `callback` must not return (not enforced by typesystem currently because `!` is unstable),
 /// unwind or otherwise return control flow to any of the previous frames.
+///
+/// # Panics
+///
+/// On x86_64, if the current thread runs with an Intel CET shadow stack. The entries of the
+/// replaced frames could never be removed from the shadow stack.
 #[cfg(switchable_stack)]
 pub unsafe fn replace_stack<F: FnOnce()>(base: *mut u8, size: usize, callback: F) -> ! {
     extern_item! { unsafe fn with_replaced_stack<F: FnOnce()>(d: usize) -> ! {
@@ -257,6 +531,8 @@ pub unsafe fn replace_stack<F: FnOnce()>(base: *mut u8, size: usize, callback: F
         ::core::ptr::read(d as *const F)();
         ::core::hint::unreachable_unchecked();
     } }
+    check_shadow_stack("replace_stack");
+    debug_check_stack("replace_stack", base, size);
     let sp = match StackDirection::new() {
         StackDirection::Ascending => base,
         StackDirection::Descending => base.offset(size as isize),
@@ -269,6 +545,135 @@ pub unsafe fn replace_stack<F: FnOnce()>(base: *mut u8, size: usize, callback: F
     );
 }
 
+/// The saved execution state of a suspended stack.
+///
+/// A `Context` is filled in by [`swap_context`] when switching away from a stack, or created by
+/// [`init_context`] for a stack that has not started running yet. Switching to it with
+/// [`swap_context`] resumes execution from where it was saved.
+#[cfg(switchable_context)]
+#[repr(C)]
+#[derive(Debug)]
+pub struct Context {
+    sp: *mut u8,
+}
+
+#[cfg(switchable_context)]
+impl Context {
+    /// Create an empty context.
+    ///
+    /// An empty context may only be used as the `from` argument to [`swap_context`], which fills
+    /// it in.
+    pub const fn new() -> Context {
+        Context {
+            sp: ::core::ptr::null_mut(),
+        }
+    }
+}
+
+#[cfg(switchable_context)]
+impl Default for Context {
+    fn default() -> Context {
+        Context::new()
+    }
+}
+
+/// Prepare a context which calls `entry(arg)` on the provided stack when first switched to.
+///
+/// `base` address must be the low address of the stack memory region, regardless of the stack
+/// growth direction. The same requirements as for [`on_stack`] apply to the region.
+///
+/// Nothing is run until the returned context is switched to with [`swap_context`]. The stack must
+/// remain allocated for as long as the context may be resumed.
+///
+/// # Unsafety
+///
+/// The stack `base` address and `size` must be aligned to [`STACK_ALIGNMENT`]. As for
+/// [`on_stack`], this is checked when debug assertions are enabled. The stack must also be large
+/// enough to hold at least a couple of frames.
+///
+/// `entry` must never return or unwind. The only way to leave it is to switch to another context
+/// with [`swap_context`].
+///
+/// # Panics
+///
+/// On x86_64, if the current thread runs with an Intel CET shadow stack. Switching contexts returns
+/// to addresses pushed on another stack, which the shadow stack does not allow.
+///
+/// # Examples
+///
+/// ```
+/// use std::alloc;
+/// const STACK_ALIGN: usize = 4096;
+/// const STACK_SIZE: usize = 64 * 1024;
+///
+/// static mut MAIN: psm::Context = psm::Context::new();
+/// static mut OTHER: psm::Context = psm::Context::new();
+///
+/// fn entry(arg: usize) -> ! {
+///     unsafe {
+///         println!("running on another stack with {}", arg);
+///         psm::swap_context(&raw mut OTHER, &raw const MAIN);
+///     }
+///     unreachable!("the context is never resumed again");
+/// }
+///
+/// unsafe {
+///     let layout = alloc::Layout::from_size_align(STACK_SIZE, STACK_ALIGN).unwrap();
+///     let new_stack = alloc::alloc(layout);
+///     assert!(!new_stack.is_null(), "allocations must succeed!");
+///     OTHER = psm::init_context(new_stack, STACK_SIZE, entry, 42);
+///     psm::swap_context(&raw mut MAIN, &raw const OTHER);
+///     alloc::dealloc(new_stack, layout);
+/// }
+/// ```
+#[cfg(switchable_context)]
+pub unsafe fn init_context(
+    base: *mut u8,
+    size: usize,
+    entry: unsafe fn(usize) -> !,
+    arg: usize,
+) -> Context {
+    type Entry = (unsafe fn(usize) -> !, usize);
+    extern_item! { unsafe fn with_context(d: usize) -> ! {
+        let (entry, arg) = ::core::ptr::read(d as *const Entry);
+        entry(arg)
+    } }
+    // All the targets implementing contexts have a descending stack. The entry point is stored at
+    // the very top of the stack, above the initial frame, and is read before anything else runs.
+    const ENTRY_SIZE: usize = 16;
+    const _: () = assert!(::core::mem::size_of::<Entry>() <= ENTRY_SIZE);
+    check_shadow_stack("init_context");
+    debug_check_stack("init_context", base, size);
+    let data = base.add(size - ENTRY_SIZE);
+    (data as *mut Entry).write((entry, arg));
+    Context {
+        sp: rust_psm_init_context(data, with_context, data as usize),
+    }
+}
+
+/// Suspend the current stack, saving its state into `from`, and resume the context `to`.
+///
+/// Callee-saved registers and the stack pointer are saved into `from`, after which the registers
+/// and stack pointer saved in `to` are restored. This function returns once some other code
+/// switches back to `from`.
+///
+/// `from` and `to` may not alias.
+///
+/// # Unsafety
+///
+/// `to` must have been filled in by a previous call to `swap_context`, or returned by
+/// [`init_context`], and must not have been resumed since. Its stack must still be allocated.
+///
+/// Any data the resumed stack refers to, including on the suspended stack, must still be valid.
+///
+/// Switching contexts is not supported on threads that run with an Intel CET shadow stack, which
+/// [`init_context`] panics on.
+#[cfg(switchable_context)]
+#[inline(always)]
+pub unsafe fn swap_context(from: *mut Context, to: *const Context) {
+    rust_psm_swap_context(from, to)
+}
+
 /// The direction into which stack grows as stack frames are made.
 ///
 /// This is a target-specific property that can be obtained at runtime by calling
@@ -297,8 +702,11 @@ impl StackDirection {
 
 /// Returns current stack pointer.
 ///
-/// Note, that the stack pointer returned is from the perspective of the caller. From the
-/// perspective of `stack_pointer` function the pointer returned is the frame pointer.
+/// Note, that the stack pointer returned is from the perspective of the caller. On targets where
+/// inline assembly is available this function is always inlined and returns the exact stack
+/// pointer of the caller. Elsewhere it calls out to an assembly routine, and from the perspective
+/// of that routine the pointer returned is the frame pointer. Under Miri it returns the
+/// approximate address of a stack frame, without any provenance.
 ///
 /// While it is a goal to minimize the amount of stack used by this function, implementations for
 /// some targets may be unable to avoid allocating a stack frame. This makes this function
@@ -315,10 +723,147 @@ impl StackDirection {
 /// 2. Callee allocates more stack than was accounted for with padding, and accesses pages outside
 ///    the stack, invalidating the execution (by e.g. crashing).
 #[cfg(asm)]
+#[inline(always)]
 pub fn stack_pointer() -> *mut u8 {
-    unsafe { rust_psm_stack_pointer() }
+    let sp: *mut u8;
+    unsafe {
+        #[cfg(all(not(miri), target_arch = "x86"))]
+        core::arch::asm!("mov {}, esp", out(reg) sp, options(nomem, nostack, preserves_flags));
+        #[cfg(all(not(miri), target_arch = "x86_64"))]
+        core::arch::asm!("mov {}, rsp", out(reg) sp, options(nomem, nostack, preserves_flags));
+        #[cfg(all(
+            not(miri),
+            any(target_arch = "arm", target_arch = "aarch64", target_arch = "arm64ec")
+        ))]
+        core::arch::asm!("mov {}, sp", out(reg) sp, options(nomem, nostack, preserves_flags));
+        #[cfg(all(not(miri), any(target_arch = "riscv32", target_arch = "riscv64")))]
+        core::arch::asm!("mv {}, sp", out(reg) sp, options(nomem, nostack, preserves_flags));
+        #[cfg(all(not(miri), target_arch = "loongarch64"))]
+        core::arch::asm!("move {}, $sp", out(reg) sp, options(nomem, nostack, preserves_flags));
+        #[cfg(all(not(miri), target_arch = "s390x"))]
+        core::arch::asm!("lgr {}, %r15", out(reg) sp, options(nomem, nostack, preserves_flags));
+        #[cfg(any(
+            miri,
+            not(any(
+                target_arch = "x86",
+                target_arch = "x86_64",
+                target_arch = "arm",
+                target_arch = "aarch64",
+                target_arch = "arm64ec",
+                target_arch = "riscv32",
+                target_arch = "riscv64",
+                target_arch = "loongarch64",
+                target_arch = "s390x",
+            ))
+        ))]
+        {
+            sp = rust_psm_stack_pointer();
+        }
+    }
+    sp
+}
+
+/// Returns the frame pointer of the caller, like `__builtin_frame_address(0)`.
+///
+/// The frame pointer points to the frame record of the calling function, which holds the frame
+/// pointer of the previous frame and the return address. Depending on the target the frame pointer
+/// points to the start of the record (x86, x86_64, AArch64) or just past its end (RISC-V,
+/// LoongArch).
+///
+/// The value is only meaningful if the calling function maintains a frame pointer, which is not
+/// the case unless the code is built with `-C force-frame-pointers=yes` on most targets.
+/// Otherwise the register may hold arbitrary data.
+///
+/// This is a call to an assembly routine rather than inline assembly, as the compiler may
+/// otherwise read the register before the calling function has set up its frame.
+///
+/// Only available on the x86, x86_64, AArch64, RISC-V and LoongArch targets other than Windows and
+/// UEFI, see `psm_frame_information!`. It is not implemented for ARM, MIPS, PowerPC, SPARC, s390x
+/// and WebAssembly, nor for the Win64 calling convention, whose frame records do not keep the frame
+/// pointer next to the return address.
+#[cfg(frame_information)]
+#[inline(always)]
+pub fn frame_pointer() -> *mut u8 {
+    unsafe { rust_psm_frame_pointer() }
 }
 
+/// Returns the return address of the caller, like `__builtin_return_address(0)`.
+///
+/// This reads the return address from the frame record that the [`frame_pointer`] of the caller
+/// points to.
+///
+/// # Unsafety
+///
+/// The calling function must maintain a frame pointer, see [`frame_pointer`]. Otherwise arbitrary
+/// memory is read.
+///
+/// Available on the same targets as [`frame_pointer`].
+#[cfg(frame_information)]
+#[inline(always)]
+pub unsafe fn return_address() -> *const u8 {
+    rust_psm_return_address()
+}
+
+/// Whether `psm::on_stack` and `psm::replace_stack` are available.
+///
+/// This is the same condition as the one checked by `psm_stack_manipulation!`, for use in const
+/// contexts. Build scripts of dependents can read it from the `DEP_PSM_0_1_SWITCHABLE_STACK`
+/// environment variable, which is either `0` or `1`.
+pub const CAN_SWITCH: bool = cfg!(switchable_stack);
+
+/// Whether `psm::on_stack_unwind` is available.
+///
+/// Build scripts of dependents can read it from the `DEP_PSM_0_1_UNWINDABLE_STACK` environment
+/// variable, which is either `0` or `1`.
+pub const CAN_UNWIND: bool = cfg!(unwindable_stack);
+
+/// Whether `psm::swap_context` and `psm::init_context` are available.
+///
+/// This is the same condition as the one checked by `psm_context_switch!`, for use in const
+/// contexts. Build scripts of dependents can read it from the `DEP_PSM_0_1_SWITCHABLE_CONTEXT`
+/// environment variable, which is either `0` or `1`.
+pub const CAN_SWITCH_CONTEXT: bool = cfg!(switchable_context);
+
+/// Whether `psm::stack_pointer` and `psm::StackDirection::new` are available.
+///
+/// This is the same condition as the one checked by `psm_stack_information!`, for use in const
+/// contexts. Build scripts of dependents can read it from the `DEP_PSM_0_1_ASM` environment
+/// variable, which is either `0` or `1`. If it is `1`, `DEP_PSM_0_1_STACK_DIRECTION` is set to
+/// either `ascending` or `descending`.
+pub const HAS_STACK_INFO: bool = cfg!(asm);
+
+/// Whether `psm::frame_pointer` and `psm::return_address` are available.
+///
+/// This is the same condition as the one checked by `psm_frame_information!`, for use in const
+/// contexts. Build scripts of dependents can read it from the `DEP_PSM_0_1_FRAME_INFORMATION`
+/// environment variable, which is either `0` or `1`.
+pub const HAS_FRAME_INFO: bool = cfg!(frame_information);
+
+/// The alignment required of both the base address and the size of the stacks passed to
+/// `psm::on_stack`, `psm::replace_stack` and `psm::init_context` on this target.
+///
+/// This is the alignment of the stack pointer required by the calling convention of the target.
+/// Stacks aligned to a page, as suggested by the guidelines of `psm::on_stack`, always satisfy it.
+pub const STACK_ALIGNMENT: usize = if cfg!(any(
+    target_arch = "arm",
+    target_arch = "mips",
+    target_arch = "mips32r6",
+    target_arch = "s390x",
+    target_arch = "sparc",
+)) {
+    8
+} else {
+    16
+};
+
+/// The recommended minimum size of stacks passed to `psm::on_stack`, `psm::replace_stack` and
+/// `psm::init_context`.
+///
+/// Smaller stacks are not rejected, but a stack of this size only leaves room for a few frames.
+/// Some architectures (such as SPARC) consume stack memory significantly faster than others, so
+/// considerably larger stacks are usually needed.
+pub const MIN_STACK_SIZE: usize = 4096;
+
 /// Macro that outputs its tokens only if `psm::on_stack` and `psm::replace_stack` are available.
 ///
 /// # Examples
@@ -361,6 +906,48 @@ macro_rules! psm_stack_manipulation {
     (yes { $($yes: tt)* } no { $($no: tt)* }) => { $($no)* };
 }
 
+/// Macro that outputs its tokens only if `psm::swap_context` and `psm::init_context` are available.
+///
+/// # Examples
+///
+/// ```
+/// # use psm::psm_context_switch;
+/// psm_context_switch! {
+///     yes {
+///         /* Functions `swap_context` and `init_context` are available here */
+///     }
+///     no {
+///         /* Functions `swap_context` and `init_context` are not available here */
+///     }
+/// }
+/// ```
+#[cfg(switchable_context)]
+#[macro_export]
+macro_rules! psm_context_switch {
+    (yes { $($yes: tt)* } no { $($no: tt)* }) => { $($yes)* };
+}
+
+/// Macro that outputs its tokens only if `psm::swap_context` and `psm::init_context` are available.
+///
+/// # Examples
+///
+/// ```
+/// # use psm::psm_context_switch;
+/// psm_context_switch! {
+///     yes {
+///         /* Functions `swap_context` and `init_context` are available here */
+///     }
+///     no {
+///         /* Functions `swap_context` and `init_context` are not available here */
+///     }
+/// }
+/// ```
+#[cfg(not(switchable_context))]
+#[macro_export]
+macro_rules! psm_context_switch {
+    (yes { $($yes: tt)* } no { $($no: tt)* }) => { $($no)* };
+}
+
 /// Macro that outputs its tokens only if `psm::stack_pointer` and `psm::StackDirection::new` are
 /// available.
 ///
@@ -404,3 +991,47 @@ macro_rules! psm_stack_information {
 macro_rules! psm_stack_information {
     (yes { $($yes: tt)* } no { $($no: tt)* }) => { $($no)* };
 }
+
+/// Macro that outputs its tokens only if `psm::frame_pointer` and `psm::return_address` are
+/// available.
+///
+/// # Examples
+///
+/// ```
+/// # use psm::psm_frame_information;
+/// psm_frame_information! {
+///     yes {
+///         /* `psm::frame_pointer` and `psm::return_address` are available here */
+///     }
+///     no {
+///         /* `psm::frame_pointer` and `psm::return_address` are not available here */
+///     }
+/// }
+/// ```
+#[cfg(frame_information)]
+#[macro_export]
+macro_rules! psm_frame_information {
+    (yes { $($yes: tt)* } no { $($no: tt)* }) => { $($yes)* };
+}
+
+/// Macro that outputs its tokens only if `psm::frame_pointer` and `psm::return_address` are
+/// available.
+///
+/// # Examples
+///
+/// ```
+/// # use psm::psm_frame_information;
+/// psm_frame_information! {
+///     yes {
+///         /* `psm::frame_pointer` and `psm::return_address` are available here */
+///     }
+///     no {
+///         /* `psm::frame_pointer` and `psm::return_address` are not available here */
+///     }
+/// }
+/// ```
+#[cfg(not(frame_information))]
+#[macro_export]
+macro_rules! psm_frame_information {
+    (yes { $($yes: tt)* } no { $($no: tt)* }) => { $($no)* };
+}
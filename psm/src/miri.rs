@@ -0,0 +1,71 @@
+//! Emulation of the stack manipulation routines under Miri, which cannot run the assembly.
+//!
+//! Miri has no way to switch stacks, so the callbacks are run directly on the current stack after
+//! checking the stack they would otherwise have been run on. This lets code using this crate be
+//! checked with Miri, but not code that relies on actually running on the new stack. Context
+//! switching is not emulated.
+//!
+//! The functions are defined with the same names and signatures as the external functions they
+//! replace, like the naked functions in `naked`.
+
+use crate::{StackDirection, STACK_ALIGNMENT};
+
+pub(crate) unsafe fn rust_psm_stack_direction() -> u8 {
+    StackDirection::Descending as u8
+}
+
+/// Returns the address of a local in this function's frame, which is about where the stack
+/// pointer of the caller would be.
+#[inline(never)]
+pub(crate) unsafe fn rust_psm_stack_pointer() -> *mut u8 {
+    let local = 0u8;
+    core::ptr::without_provenance_mut((&local as *const u8).addr())
+}
+
+/// Panics if `ptr`, one of the ends of a stack given to `psm::{function}`, is not aligned as the
+/// assembly would require it to be. Unlike `debug_check_stack`, this is checked even without debug
+/// assertions, and its messages start the same way, so that either check may catch a bad stack.
+fn check_alignment(function: &str, end: &str, ptr: *mut u8) {
+    assert!(
+        ptr.addr().is_multiple_of(STACK_ALIGNMENT),
+        "psm::{}: the stack {} {:p} is not aligned to {} bytes",
+        function,
+        end,
+        ptr,
+        STACK_ALIGNMENT
+    );
+}
+
+pub(crate) unsafe fn rust_psm_on_stack(
+    data: usize,
+    return_ptr: usize,
+    callback: extern_item!(unsafe fn(usize, usize)),
+    sp: *mut u8,
+    stack_base: *mut u8,
+) {
+    check_alignment("on_stack", "base", stack_base);
+    check_alignment("on_stack", "end", sp);
+    callback(data, return_ptr)
+}
+
+#[cfg(unwindable_stack)]
+pub(crate) unsafe fn rust_psm_on_stack_unwind(
+    data: usize,
+    return_ptr: usize,
+    callback: extern_item_unwind!(unsafe fn(usize, usize)),
+    sp: *mut u8,
+) {
+    check_alignment("on_stack_unwind", "end", sp);
+    callback(data, return_ptr)
+}
+
+pub(crate) unsafe fn rust_psm_replace_stack(
+    data: usize,
+    callback: extern_item!(unsafe fn(usize) -> !),
+    sp: *mut u8,
+    stack_base: *mut u8,
+) -> ! {
+    check_alignment("replace_stack", "base", stack_base);
+    check_alignment("replace_stack", "end", sp);
+    callback(data)
+}
@@ -0,0 +1,227 @@
+//! See `src/arch/aarch_aapcs64.s`, including for the branch target identification and return
+//! address signing.
+
+use crate::{Context, StackDirection};
+use core::arch::naked_asm;
+
+/// The landing pad at the start of every function, and the instructions signing and authenticating
+/// the link register in `on_stack`, spelled as hints like in the assembly file.
+macro_rules! bti_c {
+    () => {
+        "hint #34" // bti c
+    };
+}
+
+macro_rules! sign_lr {
+    () => {
+        "hint #25\n.cfi_negate_ra_state" // paciasp
+    };
+}
+
+macro_rules! auth_lr {
+    () => {
+        "hint #29\n.cfi_negate_ra_state" // autiasp
+    };
+}
+
+#[unsafe(naked)]
+pub(crate) unsafe extern "C" fn rust_psm_stack_direction() -> u8 {
+    naked_asm!(
+        ".cfi_startproc",
+        bti_c!(),
+        "orr w0, wzr, #{direction}",
+        "ret",
+        ".cfi_endproc",
+        direction = const StackDirection::Descending as u8,
+    )
+}
+
+#[cfg(frame_information)]
+#[unsafe(naked)]
+pub(crate) unsafe extern "C" fn rust_psm_frame_pointer() -> *mut u8 {
+    naked_asm!(
+        ".cfi_startproc",
+        bti_c!(),
+        "mov x0, x29",
+        "ret",
+        ".cfi_endproc",
+    )
+}
+
+#[cfg(frame_information)]
+#[unsafe(naked)]
+pub(crate) unsafe extern "C" fn rust_psm_return_address() -> *const u8 {
+    naked_asm!(
+        ".cfi_startproc",
+        bti_c!(),
+        "ldr x0, [x29, #8]",
+        "ret",
+        ".cfi_endproc",
+    )
+}
+
+#[unsafe(naked)]
+pub(crate) unsafe extern "C" fn _rust_psm_replace_stack(
+    data: usize,
+    callback: unsafe extern "C" fn(usize) -> !,
+    sp: *mut u8,
+) -> ! {
+    // The callback is called rather than tail-called, see `psm.h`.
+    naked_asm!(
+        ".cfi_startproc",
+        bti_c!(),
+        ".cfi_undefined x30",
+        "mov sp, x2",
+        "mov x29, xzr",
+        "blr x1",
+        "brk #1",
+        ".cfi_endproc",
+    )
+}
+
+// The old stack pointer is kept in x19, while x29 points to a copy of the caller's frame record,
+// see `psm.h`.
+macro_rules! on_stack {
+    ($name: ident, $abi: literal) => {
+        #[unsafe(naked)]
+        pub(crate) unsafe extern $abi fn $name(
+            data: usize,
+            return_ptr: usize,
+            callback: unsafe extern $abi fn(usize, usize),
+            sp: *mut u8,
+        ) {
+            naked_asm!(
+                ".cfi_startproc",
+                sign_lr!(),
+                "stp x29, x30, [sp, #-32]!",
+                ".cfi_def_cfa sp, 32",
+                ".cfi_offset x29, -32",
+                ".cfi_offset x30, -24",
+                "str x19, [sp, #16]",
+                ".cfi_offset x19, -16",
+                "mov x19, sp",
+                ".cfi_def_cfa x19, 32",
+                "stp x29, x30, [x3, #-16]!",
+                "mov x29, x3",
+                "mov sp, x3",
+                "blr x2",
+                "mov sp, x19",
+                ".cfi_def_cfa sp, 32",
+                "ldr x19, [sp, #16]",
+                ".cfi_restore x19",
+                "ldp x29, x30, [sp], #32",
+                ".cfi_def_cfa sp, 0",
+                ".cfi_restore x29",
+                ".cfi_restore x30",
+                auth_lr!(),
+                "ret",
+                ".cfi_endproc",
+            )
+        }
+    };
+}
+
+on_stack!(_rust_psm_on_stack, "C");
+on_stack!(rust_psm_on_stack_unwind, "C-unwind");
+
+#[unsafe(naked)]
+pub(crate) unsafe extern "C" fn rust_psm_swap_context(from: *mut Context, to: *const Context) {
+    // Store the callee-saved registers onto the current stack, save the stack pointer into `from`
+    // and load the same set of registers from the stack saved in `to`.
+    naked_asm!(
+        ".cfi_startproc",
+        bti_c!(),
+        "sub sp, sp, #160",
+        ".cfi_def_cfa_offset 160",
+        "stp x19, x20, [sp, #0]",
+        "stp x21, x22, [sp, #16]",
+        "stp x23, x24, [sp, #32]",
+        "stp x25, x26, [sp, #48]",
+        "stp x27, x28, [sp, #64]",
+        "stp x29, x30, [sp, #80]",
+        "stp d8, d9, [sp, #96]",
+        "stp d10, d11, [sp, #112]",
+        "stp d12, d13, [sp, #128]",
+        "stp d14, d15, [sp, #144]",
+        ".cfi_offset x19, -160",
+        ".cfi_offset x20, -152",
+        ".cfi_offset x21, -144",
+        ".cfi_offset x22, -136",
+        ".cfi_offset x23, -128",
+        ".cfi_offset x24, -120",
+        ".cfi_offset x25, -112",
+        ".cfi_offset x26, -104",
+        ".cfi_offset x27, -96",
+        ".cfi_offset x28, -88",
+        ".cfi_offset x29, -80",
+        ".cfi_offset x30, -72",
+        "mov x9, sp",
+        "str x9, [x0]",
+        "ldr x9, [x1]",
+        "mov sp, x9",
+        "ldp x19, x20, [sp, #0]",
+        "ldp x21, x22, [sp, #16]",
+        "ldp x23, x24, [sp, #32]",
+        "ldp x25, x26, [sp, #48]",
+        "ldp x27, x28, [sp, #64]",
+        "ldp x29, x30, [sp, #80]",
+        "ldp d8, d9, [sp, #96]",
+        "ldp d10, d11, [sp, #112]",
+        "ldp d12, d13, [sp, #128]",
+        "ldp d14, d15, [sp, #144]",
+        "add sp, sp, #160",
+        ".cfi_def_cfa_offset 0",
+        "ret",
+        ".cfi_endproc",
+    )
+}
+
+/// Loads the address of `context_start` into x9. `adr` can't be used, as the function may be
+/// placed in another section.
+macro_rules! load_context_start {
+    () => {
+        "adrp x9, {start}\nadd x9, x9, :lo12:{start}"
+    };
+}
+
+#[unsafe(naked)]
+pub(crate) unsafe extern "C" fn rust_psm_init_context(
+    sp: *mut u8,
+    callback: unsafe extern "C" fn(usize) -> !,
+    data: usize,
+) -> *mut u8 {
+    // Lay out a frame below x0 that looks as if `rust_psm_swap_context` had been called from
+    // `context_start`, with the argument in x19 and the callback in x20.
+    naked_asm!(
+        ".cfi_startproc",
+        bti_c!(),
+        "sub x0, x0, #160",
+        "stp x2, x1, [x0, #0]",
+        "stp xzr, xzr, [x0, #16]",
+        "stp xzr, xzr, [x0, #32]",
+        "stp xzr, xzr, [x0, #48]",
+        "stp xzr, xzr, [x0, #64]",
+        load_context_start!(),
+        "stp xzr, x9, [x0, #80]",
+        "stp xzr, xzr, [x0, #96]",
+        "stp xzr, xzr, [x0, #112]",
+        "stp xzr, xzr, [x0, #128]",
+        "stp xzr, xzr, [x0, #144]",
+        "ret",
+        ".cfi_endproc",
+        start = sym context_start,
+    )
+}
+
+/// Not a real function: the first `rust_psm_swap_context` to a new context returns here.
+#[unsafe(naked)]
+unsafe extern "C" fn context_start() -> ! {
+    naked_asm!(
+        ".cfi_startproc",
+        ".cfi_undefined x30",
+        "mov x0, x19",
+        "blr x20",
+        "brk #1",
+        ".cfi_endproc",
+    )
+}
@@ -0,0 +1,79 @@
+//! See `src/arch/arm_aapcs.s`.
+//!
+//! Besides the DWARF CFI, the functions carry entries for the ARM exception handling tables, which
+//! are what unwinders consult on this architecture.
+
+use crate::StackDirection;
+use core::arch::naked_asm;
+
+#[unsafe(naked)]
+pub(crate) unsafe extern "aapcs" fn rust_psm_stack_direction() -> u8 {
+    naked_asm!(
+        ".fnstart",
+        ".cfi_startproc",
+        // movs to support Thumb-1
+        "movs r0, #{direction}",
+        "bx lr",
+        ".cfi_endproc",
+        ".cantunwind",
+        ".fnend",
+        direction = const StackDirection::Descending as u8,
+    )
+}
+
+#[unsafe(naked)]
+pub(crate) unsafe extern "aapcs" fn _rust_psm_replace_stack(
+    data: usize,
+    callback: unsafe extern "aapcs" fn(usize) -> !,
+    sp: *mut u8,
+) -> ! {
+    // The callback is called rather than tail-called, see `psm.h`. The function is also
+    // `.cantunwind` in the ARM unwind tables.
+    naked_asm!(
+        ".fnstart",
+        ".cfi_startproc",
+        ".cfi_undefined lr",
+        "mov sp, r2",
+        "blx r1",
+        "udf #0",
+        ".cfi_endproc",
+        ".cantunwind",
+        ".fnend",
+    )
+}
+
+// The unwind table entry lets panics unwind out of the callback through this frame.
+macro_rules! on_stack {
+    ($name: ident, $abi: literal) => {
+        #[unsafe(naked)]
+        pub(crate) unsafe extern $abi fn $name(
+            data: usize,
+            return_ptr: usize,
+            callback: unsafe extern $abi fn(usize, usize),
+            sp: *mut u8,
+        ) {
+            naked_asm!(
+                ".fnstart",
+                ".cfi_startproc",
+                "push {{r4, lr}}",
+                ".save {{r4, lr}}",
+                ".cfi_def_cfa_offset 8",
+                "mov r4, sp",
+                ".setfp r4, sp",
+                ".cfi_def_cfa_register r4",
+                ".cfi_offset lr, -4",
+                ".cfi_offset r4, -8",
+                "mov sp, r3",
+                "blx r2",
+                "mov sp, r4",
+                ".cfi_restore sp",
+                "pop {{r4, pc}}",
+                ".cfi_endproc",
+                ".fnend",
+            )
+        }
+    };
+}
+
+on_stack!(_rust_psm_on_stack, "aapcs");
+on_stack!(rust_psm_on_stack_unwind, "aapcs-unwind");
@@ -0,0 +1,37 @@
+//! Implementations of the stack manipulation routines as naked functions.
+//!
+//! These are used instead of the assembly files in `src/arch` on the most common targets, so that
+//! building this crate does not require a C toolchain and the routines take part in (cross
+//! language) LTO. They are the same routines as in the corresponding assembly files, which are
+//! still used for all the other targets, or when the `PSM_NO_NAKED` environment variable is set
+//! during the build.
+//!
+//! The functions are defined with the same names and signatures as the external functions they
+//! replace. `rust_psm_stack_pointer` is not needed, as `stack_pointer` uses inline assembly on all
+//! of these targets. `rust_psm_on_stack` is defined twice, the second time with an ABI that permits
+//! unwinding, as a function cannot be declared with two different ABIs like an external one.
+//!
+//! The conventions the routines follow for unwinders and profilers are described in
+//! `src/arch/psm.h`.
+
+#[cfg(target_arch = "x86_64")]
+#[path = "x86_64.rs"]
+mod imp;
+
+#[cfg(target_arch = "x86")]
+#[path = "x86.rs"]
+mod imp;
+
+#[cfg(target_arch = "aarch64")]
+#[path = "aarch64.rs"]
+mod imp;
+
+#[cfg(target_arch = "arm")]
+#[path = "arm.rs"]
+mod imp;
+
+#[cfg(target_arch = "riscv64")]
+#[path = "riscv64.rs"]
+mod imp;
+
+pub(crate) use self::imp::*;
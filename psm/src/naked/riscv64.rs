@@ -0,0 +1,242 @@
+//! See `src/arch/riscv64.s`.
+
+use crate::{Context, StackDirection};
+use core::arch::naked_asm;
+
+#[unsafe(naked)]
+pub(crate) unsafe extern "C" fn rust_psm_stack_direction() -> u8 {
+    naked_asm!(
+        ".cfi_startproc",
+        "li x10, {direction}",
+        "jr x1",
+        ".cfi_endproc",
+        direction = const StackDirection::Descending as u8,
+    )
+}
+
+#[cfg(frame_information)]
+#[unsafe(naked)]
+pub(crate) unsafe extern "C" fn rust_psm_frame_pointer() -> *mut u8 {
+    naked_asm!(".cfi_startproc", "add x10, x8, x0", "jr x1", ".cfi_endproc")
+}
+
+#[cfg(frame_information)]
+#[unsafe(naked)]
+pub(crate) unsafe extern "C" fn rust_psm_return_address() -> *const u8 {
+    naked_asm!(".cfi_startproc", "ld x10, -8(x8)", "jr x1", ".cfi_endproc")
+}
+
+#[unsafe(naked)]
+pub(crate) unsafe extern "C" fn _rust_psm_replace_stack(
+    data: usize,
+    callback: unsafe extern "C" fn(usize) -> !,
+    sp: *mut u8,
+) -> ! {
+    // The callback is called rather than tail-called, see `psm.h`.
+    naked_asm!(
+        ".cfi_startproc",
+        ".cfi_undefined x1",
+        "add x2, x12, x0",
+        "add x8, x0, x0",
+        "jalr x1, x11, 0",
+        "unimp",
+        ".cfi_endproc",
+    )
+}
+
+// x8 points to a copy of the caller's frame record at the top of the new stack, see `psm.h`. The
+// old stack pointer is saved just below it.
+macro_rules! on_stack {
+    ($name: ident, $abi: literal) => {
+        #[unsafe(naked)]
+        pub(crate) unsafe extern $abi fn $name(
+            data: usize,
+            return_ptr: usize,
+            callback: unsafe extern $abi fn(usize, usize),
+            sp: *mut u8,
+        ) {
+            naked_asm!(
+                ".cfi_startproc",
+                "sd x1, -8(x13)",
+                "sd x8, -16(x13)",
+                "sd x2, -24(x13)",
+                "addi x8, x13, 0",
+                ".cfi_def_cfa x8, 0",
+                ".cfi_offset x1, -8",
+                ".cfi_offset x8, -16",
+                ".cfi_offset x2, -24",
+                "addi x2, x13, -32",
+                "jalr x1, x12, 0",
+                "ld x1, -8(x8)",
+                ".cfi_restore x1",
+                "ld x2, -24(x8)",
+                ".cfi_def_cfa x2, 0",
+                "ld x8, -16(x8)",
+                ".cfi_restore x8",
+                "jr x1",
+                ".cfi_endproc",
+            )
+        }
+    };
+}
+
+on_stack!(_rust_psm_on_stack, "C");
+on_stack!(rust_psm_on_stack_unwind, "C-unwind");
+
+/// Saves the callee-saved floating point registers, if the target has any. The assembler is not
+/// told about the target features, so the extension is enabled explicitly.
+#[cfg(target_feature = "d")]
+macro_rules! save_fp_registers {
+    () => {
+        ".option push
+        .option arch, +d
+        fsd f8, 104(x2)
+        fsd f9, 112(x2)
+        fsd f18, 120(x2)
+        fsd f19, 128(x2)
+        fsd f20, 136(x2)
+        fsd f21, 144(x2)
+        fsd f22, 152(x2)
+        fsd f23, 160(x2)
+        fsd f24, 168(x2)
+        fsd f25, 176(x2)
+        fsd f26, 184(x2)
+        fsd f27, 192(x2)
+        .option pop"
+    };
+}
+
+/// Restores the callee-saved floating point registers, if the target has any.
+#[cfg(target_feature = "d")]
+macro_rules! restore_fp_registers {
+    () => {
+        ".option push
+        .option arch, +d
+        fld f8, 104(x2)
+        fld f9, 112(x2)
+        fld f18, 120(x2)
+        fld f19, 128(x2)
+        fld f20, 136(x2)
+        fld f21, 144(x2)
+        fld f22, 152(x2)
+        fld f23, 160(x2)
+        fld f24, 168(x2)
+        fld f25, 176(x2)
+        fld f26, 184(x2)
+        fld f27, 192(x2)
+        .option pop"
+    };
+}
+
+#[cfg(not(target_feature = "d"))]
+macro_rules! save_fp_registers {
+    () => {
+        ""
+    };
+}
+
+#[cfg(not(target_feature = "d"))]
+macro_rules! restore_fp_registers {
+    () => {
+        ""
+    };
+}
+
+#[unsafe(naked)]
+pub(crate) unsafe extern "C" fn rust_psm_swap_context(from: *mut Context, to: *const Context) {
+    // Store the callee-saved registers onto the current stack, save the stack pointer into `from`
+    // and load the same set of registers from the stack saved in `to`.
+    naked_asm!(
+        ".cfi_startproc",
+        "addi x2, x2, -208",
+        ".cfi_def_cfa_offset 208",
+        "sd x1, 0(x2)",
+        "sd x8, 8(x2)",
+        "sd x9, 16(x2)",
+        "sd x18, 24(x2)",
+        "sd x19, 32(x2)",
+        "sd x20, 40(x2)",
+        "sd x21, 48(x2)",
+        "sd x22, 56(x2)",
+        "sd x23, 64(x2)",
+        "sd x24, 72(x2)",
+        "sd x25, 80(x2)",
+        "sd x26, 88(x2)",
+        "sd x27, 96(x2)",
+        ".cfi_offset x1, -208",
+        ".cfi_offset x8, -200",
+        ".cfi_offset x9, -192",
+        ".cfi_offset x18, -184",
+        ".cfi_offset x19, -176",
+        ".cfi_offset x20, -168",
+        ".cfi_offset x21, -160",
+        ".cfi_offset x22, -152",
+        ".cfi_offset x23, -144",
+        ".cfi_offset x24, -136",
+        ".cfi_offset x25, -128",
+        ".cfi_offset x26, -120",
+        ".cfi_offset x27, -112",
+        save_fp_registers!(),
+        "sd x2, 0(x10)",
+        "ld x2, 0(x11)",
+        "ld x1, 0(x2)",
+        "ld x8, 8(x2)",
+        "ld x9, 16(x2)",
+        "ld x18, 24(x2)",
+        "ld x19, 32(x2)",
+        "ld x20, 40(x2)",
+        "ld x21, 48(x2)",
+        "ld x22, 56(x2)",
+        "ld x23, 64(x2)",
+        "ld x24, 72(x2)",
+        "ld x25, 80(x2)",
+        "ld x26, 88(x2)",
+        "ld x27, 96(x2)",
+        restore_fp_registers!(),
+        "addi x2, x2, 208",
+        ".cfi_def_cfa_offset 0",
+        "jr x1",
+        ".cfi_endproc",
+    )
+}
+
+#[unsafe(naked)]
+pub(crate) unsafe extern "C" fn rust_psm_init_context(
+    sp: *mut u8,
+    callback: unsafe extern "C" fn(usize) -> !,
+    data: usize,
+) -> *mut u8 {
+    // Lay out a frame below x10 that looks as if `rust_psm_swap_context` had been called from
+    // `context_start`, with the argument in x9 (s1) and the callback in x18 (s2). The remaining
+    // registers start out zeroed.
+    naked_asm!(
+        ".cfi_startproc",
+        "addi x14, x10, -208",
+        "addi x13, x14, 0",
+        "2:",
+        "sd x0, 0(x13)",
+        "addi x13, x13, 8",
+        "bltu x13, x10, 2b",
+        "addi x10, x14, 0",
+        "lla x13, {start}",
+        "sd x13, 0(x10)",
+        "sd x12, 16(x10)",
+        "sd x11, 24(x10)",
+        "jr x1",
+        ".cfi_endproc",
+        start = sym context_start,
+    )
+}
+
+/// Not a real function: the first `rust_psm_swap_context` to a new context returns here.
+#[unsafe(naked)]
+unsafe extern "C" fn context_start() -> ! {
+    naked_asm!(
+        ".cfi_startproc",
+        ".cfi_undefined x1",
+        "add x10, x9, x0",
+        "jalr x1, x18, 0",
+        "unimp",
+        ".cfi_endproc",
+    )
+}
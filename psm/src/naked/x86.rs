@@ -0,0 +1,191 @@
+//! See `src/arch/x86.s`.
+
+use crate::{Context, StackDirection};
+use core::arch::naked_asm;
+
+#[unsafe(naked)]
+pub(crate) unsafe extern "fastcall" fn rust_psm_stack_direction() -> u8 {
+    naked_asm!(
+        ".cfi_startproc",
+        "movb ${direction}, %al",
+        "retl",
+        ".cfi_endproc",
+        direction = const StackDirection::Descending as u8,
+        options(att_syntax)
+    )
+}
+
+#[cfg(frame_information)]
+#[unsafe(naked)]
+pub(crate) unsafe extern "fastcall" fn rust_psm_frame_pointer() -> *mut u8 {
+    naked_asm!(
+        ".cfi_startproc",
+        "movl %ebp, %eax",
+        "retl",
+        ".cfi_endproc",
+        options(att_syntax)
+    )
+}
+
+#[cfg(frame_information)]
+#[unsafe(naked)]
+pub(crate) unsafe extern "fastcall" fn rust_psm_return_address() -> *const u8 {
+    naked_asm!(
+        ".cfi_startproc",
+        "movl 4(%ebp), %eax",
+        "retl",
+        ".cfi_endproc",
+        options(att_syntax)
+    )
+}
+
+#[unsafe(naked)]
+pub(crate) unsafe extern "fastcall" fn _rust_psm_replace_stack(
+    data: usize,
+    callback: unsafe extern "fastcall" fn(usize) -> !,
+    sp: *mut u8,
+) -> ! {
+    // The callee expects the stack to be offset by 4 bytes off the required alignment on entry, as
+    // if a return address had been pushed, so the callback is called rather than jumped to. The
+    // return address points back into this function, which marks the end of the stack for
+    // unwinders by leaving the return address undefined. The frame pointer is cleared for the same
+    // reason.
+    naked_asm!(
+        ".cfi_startproc",
+        ".cfi_undefined %eip",
+        "movl 4(%esp), %esp",
+        "xorl %ebp, %ebp",
+        "calll *%edx",
+        "ud2",
+        ".cfi_endproc",
+        options(att_syntax)
+    )
+}
+
+// The old stack pointer is kept in %ebx, while %ebp points to a copy of the caller's frame record,
+// see `psm.h`.
+macro_rules! on_stack {
+    ($name: ident, $abi: literal) => {
+        #[unsafe(naked)]
+        pub(crate) unsafe extern $abi fn $name(
+            data: usize,
+            return_ptr: usize,
+            callback: unsafe extern $abi fn(usize, usize),
+            sp: *mut u8,
+        ) {
+            naked_asm!(
+                ".cfi_startproc",
+                "pushl %ebp",
+                ".cfi_def_cfa %esp, 8",
+                ".cfi_offset %ebp, -8",
+                "pushl %ebx",
+                ".cfi_def_cfa %esp, 12",
+                ".cfi_offset %ebx, -12",
+                "movl %esp, %ebx",
+                ".cfi_def_cfa_register %ebx",
+                "movl 16(%ebx), %esp",
+                "pushl 8(%ebx)",
+                "pushl %ebp",
+                "movl %esp, %ebp",
+                "subl $8, %esp",
+                "calll *12(%ebx)",
+                "movl %ebx, %esp",
+                ".cfi_def_cfa_register %esp",
+                "popl %ebx",
+                ".cfi_def_cfa_offset 8",
+                ".cfi_restore %ebx",
+                "popl %ebp",
+                ".cfi_def_cfa_offset 4",
+                ".cfi_restore %ebp",
+                "retl $8",
+                ".cfi_endproc",
+                options(att_syntax)
+            )
+        }
+    };
+}
+
+on_stack!(_rust_psm_on_stack, "fastcall");
+on_stack!(rust_psm_on_stack_unwind, "fastcall-unwind");
+
+#[unsafe(naked)]
+pub(crate) unsafe extern "fastcall" fn rust_psm_swap_context(
+    from: *mut Context,
+    to: *const Context,
+) {
+    // Push the callee-saved registers onto the current stack, save the stack pointer into `from`
+    // and pop the same set of registers from the stack saved in `to`.
+    naked_asm!(
+        ".cfi_startproc",
+        "pushl %ebp",
+        ".cfi_def_cfa_offset 8",
+        "pushl %ebx",
+        ".cfi_def_cfa_offset 12",
+        "pushl %esi",
+        ".cfi_def_cfa_offset 16",
+        "pushl %edi",
+        ".cfi_def_cfa_offset 20",
+        ".cfi_offset %ebp, -8",
+        ".cfi_offset %ebx, -12",
+        ".cfi_offset %esi, -16",
+        ".cfi_offset %edi, -20",
+        "movl %esp, (%ecx)",
+        "movl (%edx), %esp",
+        "popl %edi",
+        ".cfi_def_cfa_offset 16",
+        "popl %esi",
+        ".cfi_def_cfa_offset 12",
+        "popl %ebx",
+        ".cfi_def_cfa_offset 8",
+        "popl %ebp",
+        ".cfi_def_cfa_offset 4",
+        "retl",
+        ".cfi_endproc",
+        options(att_syntax)
+    )
+}
+
+#[unsafe(naked)]
+pub(crate) unsafe extern "fastcall" fn rust_psm_init_context(
+    sp: *mut u8,
+    callback: unsafe extern "fastcall" fn(usize) -> !,
+    data: usize,
+) -> *mut u8 {
+    // Lay out a frame below %ecx that looks as if `rust_psm_swap_context` had been called from
+    // `context_start`, with the callback in %esi and its argument in %ebx. The frame is placed so
+    // that the stack is 16-byte aligned at the point the callback is called.
+    naked_asm!(
+        ".cfi_startproc",
+        "leal -36(%ecx), %eax",
+        "movl $0, (%eax)",    // edi
+        "movl %edx, 4(%eax)", // esi
+        "movl 4(%esp), %edx",
+        "movl %edx, 8(%eax)", // ebx
+        "movl $0, 12(%eax)",  // ebp
+        "calll 2f",
+        "2:",
+        ".cfi_adjust_cfa_offset 4",
+        "popl %edx",
+        ".cfi_adjust_cfa_offset -4",
+        "leal ({start}-2b)(%edx), %edx",
+        "movl %edx, 16(%eax)", // return address
+        "retl $4",
+        ".cfi_endproc",
+        start = sym context_start,
+        options(att_syntax)
+    )
+}
+
+/// Not a real function: the first `rust_psm_swap_context` to a new context returns here.
+#[unsafe(naked)]
+unsafe extern "fastcall" fn context_start() -> ! {
+    naked_asm!(
+        ".cfi_startproc",
+        ".cfi_undefined %eip",
+        "movl %ebx, %ecx",
+        "calll *%esi",
+        "ud2",
+        ".cfi_endproc",
+        options(att_syntax)
+    )
+}
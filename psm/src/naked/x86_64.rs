@@ -0,0 +1,209 @@
+//! See `src/arch/x86_64.s`, including for the `endbr64` at the start of each function.
+
+use crate::{Context, StackDirection};
+use core::arch::naked_asm;
+
+#[unsafe(naked)]
+pub(crate) unsafe extern "sysv64" fn rust_psm_stack_direction() -> u8 {
+    naked_asm!(
+        ".cfi_startproc",
+        "endbr64",
+        "movb ${direction}, %al",
+        "retq",
+        ".cfi_endproc",
+        direction = const StackDirection::Descending as u8,
+        options(att_syntax)
+    )
+}
+
+#[cfg(frame_information)]
+#[unsafe(naked)]
+pub(crate) unsafe extern "sysv64" fn rust_psm_frame_pointer() -> *mut u8 {
+    naked_asm!(
+        ".cfi_startproc",
+        "endbr64",
+        "movq %rbp, %rax",
+        "retq",
+        ".cfi_endproc",
+        options(att_syntax)
+    )
+}
+
+#[cfg(frame_information)]
+#[unsafe(naked)]
+pub(crate) unsafe extern "sysv64" fn rust_psm_return_address() -> *const u8 {
+    naked_asm!(
+        ".cfi_startproc",
+        "endbr64",
+        "movq 8(%rbp), %rax",
+        "retq",
+        ".cfi_endproc",
+        options(att_syntax)
+    )
+}
+
+#[unsafe(naked)]
+pub(crate) unsafe extern "sysv64" fn _rust_psm_replace_stack(
+    data: usize,
+    callback: unsafe extern "sysv64" fn(usize) -> !,
+    sp: *mut u8,
+) -> ! {
+    // The callback is called rather than tail-called, see `psm.h`.
+    naked_asm!(
+        ".cfi_startproc",
+        "endbr64",
+        ".cfi_undefined %rip",
+        "movq %rdx, %rsp",
+        "xorl %ebp, %ebp",
+        "callq *%rsi",
+        "ud2",
+        ".cfi_endproc",
+        options(att_syntax)
+    )
+}
+
+// The old stack pointer is kept in %rbx, while %rbp points to a copy of the caller's frame record,
+// see `psm.h`.
+macro_rules! on_stack {
+    ($name: ident, $abi: literal) => {
+        #[unsafe(naked)]
+        pub(crate) unsafe extern $abi fn $name(
+            data: usize,
+            return_ptr: usize,
+            callback: unsafe extern $abi fn(usize, usize),
+            sp: *mut u8,
+        ) {
+            naked_asm!(
+                ".cfi_startproc",
+                "endbr64",
+                "pushq %rbp",
+                ".cfi_def_cfa %rsp, 16",
+                ".cfi_offset %rbp, -16",
+                "pushq %rbx",
+                ".cfi_def_cfa %rsp, 24",
+                ".cfi_offset %rbx, -24",
+                "movq %rsp, %rbx",
+                ".cfi_def_cfa_register %rbx",
+                "movq 16(%rbx), %rax",
+                "movq %rax, -8(%rcx)",
+                "movq %rbp, -16(%rcx)",
+                "leaq -16(%rcx), %rbp",
+                "movq %rbp, %rsp",
+                "callq *%rdx",
+                "movq %rbx, %rsp",
+                ".cfi_def_cfa_register %rsp",
+                "popq %rbx",
+                ".cfi_def_cfa_offset 16",
+                ".cfi_restore %rbx",
+                "popq %rbp",
+                ".cfi_def_cfa_offset 8",
+                ".cfi_restore %rbp",
+                "retq",
+                ".cfi_endproc",
+                options(att_syntax)
+            )
+        }
+    };
+}
+
+on_stack!(_rust_psm_on_stack, "sysv64");
+on_stack!(rust_psm_on_stack_unwind, "sysv64-unwind");
+
+#[unsafe(naked)]
+pub(crate) unsafe extern "sysv64" fn rust_psm_swap_context(from: *mut Context, to: *const Context) {
+    // Push the callee-saved registers and the SSE/x87 control words onto the current stack, save
+    // the stack pointer into `from` and pop the same set of registers from the stack saved in
+    // `to`.
+    naked_asm!(
+        ".cfi_startproc",
+        "endbr64",
+        "pushq %rbp",
+        ".cfi_def_cfa_offset 16",
+        "pushq %rbx",
+        ".cfi_def_cfa_offset 24",
+        "pushq %r12",
+        ".cfi_def_cfa_offset 32",
+        "pushq %r13",
+        ".cfi_def_cfa_offset 40",
+        "pushq %r14",
+        ".cfi_def_cfa_offset 48",
+        "pushq %r15",
+        ".cfi_def_cfa_offset 56",
+        "subq $8, %rsp",
+        ".cfi_def_cfa_offset 64",
+        ".cfi_offset %rbp, -16",
+        ".cfi_offset %rbx, -24",
+        ".cfi_offset %r12, -32",
+        ".cfi_offset %r13, -40",
+        ".cfi_offset %r14, -48",
+        ".cfi_offset %r15, -56",
+        "stmxcsr (%rsp)",
+        "fnstcw 4(%rsp)",
+        "movq %rsp, (%rdi)",
+        "movq (%rsi), %rsp",
+        "ldmxcsr (%rsp)",
+        "fldcw 4(%rsp)",
+        "addq $8, %rsp",
+        ".cfi_def_cfa_offset 56",
+        "popq %r15",
+        ".cfi_def_cfa_offset 48",
+        "popq %r14",
+        ".cfi_def_cfa_offset 40",
+        "popq %r13",
+        ".cfi_def_cfa_offset 32",
+        "popq %r12",
+        ".cfi_def_cfa_offset 24",
+        "popq %rbx",
+        ".cfi_def_cfa_offset 16",
+        "popq %rbp",
+        ".cfi_def_cfa_offset 8",
+        "retq",
+        ".cfi_endproc",
+        options(att_syntax)
+    )
+}
+
+#[unsafe(naked)]
+pub(crate) unsafe extern "sysv64" fn rust_psm_init_context(
+    sp: *mut u8,
+    callback: unsafe extern "sysv64" fn(usize) -> !,
+    data: usize,
+) -> *mut u8 {
+    // Lay out a frame below %rdi that looks as if `rust_psm_swap_context` had been called from
+    // `context_start`, with the callback in %r12 and its argument in %rbx. The control words are
+    // copied from the current thread.
+    naked_asm!(
+        ".cfi_startproc",
+        "endbr64",
+        "leaq -80(%rdi), %rax",
+        "stmxcsr (%rax)",
+        "fnstcw 4(%rax)",
+        "movq $0, 8(%rax)",  // r15
+        "movq $0, 16(%rax)", // r14
+        "movq $0, 24(%rax)", // r13
+        "movq %rsi, 32(%rax)", // r12
+        "movq %rdx, 40(%rax)", // rbx
+        "movq $0, 48(%rax)", // rbp
+        "leaq {start}(%rip), %rcx",
+        "movq %rcx, 56(%rax)", // return address
+        "movq $0, 64(%rax)",
+        "retq",
+        ".cfi_endproc",
+        start = sym context_start,
+        options(att_syntax)
+    )
+}
+
+/// Not a real function: the first `rust_psm_swap_context` to a new context returns here.
+#[unsafe(naked)]
+unsafe extern "sysv64" fn context_start() -> ! {
+    naked_asm!(
+        ".cfi_startproc",
+        ".cfi_undefined %rip",
+        "movq %rbx, %rdi",
+        "callq *%r12",
+        "ud2",
+        ".cfi_endproc",
+        options(att_syntax)
+    )
+}
@@ -0,0 +1,212 @@
+/// An owned memory region suitable to be used as a stack.
+///
+/// The region is aligned to, and its size is a multiple of, the page size. On Unix targets with
+/// the `std` feature enabled the memory is mapped directly from the operating system, with a guard
+/// page (not writable, readable or executable) on either side of the usable region, so that
+/// overflowing the stack results in a crash rather than silently corrupting other memory.
+/// Elsewhere, and under Miri, the memory comes from the global allocator and no guard pages are
+/// set up.
+///
+/// The memory is released when the `Stack` is dropped.
+#[derive(Debug)]
+pub struct Stack {
+    allocation: *mut u8,
+    allocation_size: usize,
+    guard_size: usize,
+}
+
+// The stack owns its memory and only hands it out through `&mut self`.
+unsafe impl Send for Stack {}
+unsafe impl Sync for Stack {}
+
+impl Stack {
+    /// Allocate a new stack with at least `size` usable bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the memory cannot be allocated.
+    pub fn new(size: usize) -> Stack {
+        let page_size = page_size();
+        let pages = size
+            .checked_add(page_size - 1)
+            .expect("unreasonably large stack requested")
+            / page_size;
+        let size = core::cmp::max(1, pages)
+            .checked_mul(page_size)
+            .expect("unreasonably large stack requested");
+        unsafe { allocate(size, page_size) }
+    }
+
+    /// The low address of the usable stack memory region, regardless of the stack growth
+    /// direction.
+    ///
+    /// Together with [`size`](Self::size) this is suitable to be passed to [`on_stack`],
+    /// [`replace_stack`] and similar functions.
+    ///
+    /// [`on_stack`]: crate::on_stack
+    /// [`replace_stack`]: crate::replace_stack
+    pub fn base(&self) -> *mut u8 {
+        self.allocation.wrapping_add(self.guard_size)
+    }
+
+    /// The size of the usable stack memory region, in bytes.
+    ///
+    /// This is the requested size rounded up to a multiple of the page size.
+    pub fn size(&self) -> usize {
+        self.allocation_size - 2 * self.guard_size
+    }
+
+    /// Run the closure on this stack.
+    ///
+    /// This is a safe version of [`on_stack`](crate::on_stack). If the closure panics, the panic
+    /// is caught on this stack and resumed after switching back to the original stack.
+    ///
+    /// Only available where the stack is surrounded by guard pages, that is on Unix targets, so
+    /// that overflowing it cannot corrupt other memory. Under Miri the closure runs on the
+    /// current stack. Elsewhere use [`on_stack_unchecked`](Self::on_stack_unchecked).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[cfg(any(unix, miri))] {
+    /// let mut stack = psm::Stack::new(64 * 1024);
+    /// let (sp, result) = stack.on_stack(|| (psm::stack_pointer(), 4 + 4));
+    /// # #[cfg(not(miri))] // The closure runs on the current stack under Miri
+    /// assert!(stack.base() < sp && sp <= stack.base().wrapping_add(stack.size()));
+    /// assert_eq!(result, 8);
+    /// # }
+    /// ```
+    // Has to be kept in sync with the `cfg` of `allocate` below.
+    #[cfg(all(feature = "std", switchable_stack, any(unix, miri)))]
+    pub fn on_stack<R, F: FnOnce() -> R>(&mut self, callback: F) -> R {
+        // Safety: overflowing the stack hits a guard page, and under Miri the stack is not used.
+        unsafe { self.on_stack_unchecked(callback) }
+    }
+
+    /// Run the closure on this stack, without guard pages on all targets.
+    ///
+    /// This is the same as [`on_stack`](Self::on_stack), but also available on targets where the
+    /// stack is allocated without guard pages.
+    ///
+    /// # Safety
+    ///
+    /// Unless the stack has guard pages (see [`Stack`]), the closure must not use more than
+    /// [`size`](Self::size) bytes of stack, as nothing stops it from overflowing into other
+    /// memory.
+    #[cfg(all(feature = "std", switchable_stack))]
+    pub unsafe fn on_stack_unchecked<R, F: FnOnce() -> R>(&mut self, callback: F) -> R {
+        use std::panic::{self, AssertUnwindSafe};
+        // Safety: the region is owned by `self`, properly aligned and, by borrowing `self`
+        // mutably, cannot be in use by another call at the same time. Unwinding out of the
+        // callback is prevented by catching it here.
+        let result = unsafe {
+            crate::on_stack(self.base(), self.size(), || {
+                panic::catch_unwind(AssertUnwindSafe(callback))
+            })
+        };
+        match result {
+            Ok(r) => r,
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+}
+
+#[cfg(all(feature = "std", unix, not(miri)))]
+unsafe fn allocate(size: usize, page_size: usize) -> Stack {
+    // One guard page below the stack and another one above it.
+    let allocation_size = size
+        .checked_add(2 * page_size)
+        .expect("unreasonably large stack requested");
+    let mapping = libc::mmap(
+        core::ptr::null_mut(),
+        allocation_size,
+        libc::PROT_NONE,
+        libc::MAP_PRIVATE | libc::MAP_ANON,
+        -1, // Some implementations assert fd = -1 if MAP_ANON is specified
+        0,
+    );
+    assert_ne!(
+        mapping,
+        libc::MAP_FAILED,
+        "mmap failed to allocate stack: {}",
+        std::io::Error::last_os_error()
+    );
+    // Construct the `Stack` first, so that the mapping is released if the assertion below fails.
+    let stack = Stack {
+        allocation: mapping as *mut u8,
+        allocation_size,
+        guard_size: page_size,
+    };
+    let usable = stack.base() as *mut libc::c_void;
+    #[cfg(not(target_os = "openbsd"))]
+    let result = libc::mprotect(usable, size, libc::PROT_READ | libc::PROT_WRITE);
+    // OpenBSD requires stack memory to be mapped with `MAP_STACK`.
+    #[cfg(target_os = "openbsd")]
+    let result = if libc::mmap(
+        usable,
+        size,
+        libc::PROT_READ | libc::PROT_WRITE,
+        libc::MAP_FIXED | libc::MAP_PRIVATE | libc::MAP_ANON | libc::MAP_STACK,
+        -1,
+        0,
+    ) == usable
+    {
+        0
+    } else {
+        -1
+    };
+    assert_ne!(
+        result,
+        -1,
+        "mprotect/mmap failed: {}",
+        std::io::Error::last_os_error()
+    );
+    stack
+}
+
+#[cfg(all(feature = "std", unix, not(miri)))]
+impl Drop for Stack {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.allocation as *mut libc::c_void, self.allocation_size);
+        }
+    }
+}
+
+#[cfg(all(feature = "std", unix, not(miri)))]
+fn page_size() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGE_SIZE) as usize }
+}
+
+#[cfg(not(all(feature = "std", unix, not(miri))))]
+unsafe fn allocate(size: usize, page_size: usize) -> Stack {
+    let layout = alloc::alloc::Layout::from_size_align(size, page_size).unwrap();
+    let allocation = alloc::alloc::alloc(layout);
+    if allocation.is_null() {
+        alloc::alloc::handle_alloc_error(layout);
+    }
+    Stack {
+        allocation,
+        allocation_size: size,
+        guard_size: 0,
+    }
+}
+
+#[cfg(not(all(feature = "std", unix, not(miri))))]
+impl Drop for Stack {
+    fn drop(&mut self) {
+        unsafe {
+            alloc::alloc::dealloc(
+                self.allocation,
+                alloc::alloc::Layout::from_size_align_unchecked(self.allocation_size, page_size()),
+            );
+        }
+    }
+}
+
+/// Without OS support we do not know the actual page size, but 4kB is a portable choice for the
+/// alignment of a stack.
+#[cfg(not(all(feature = "std", unix, not(miri))))]
+fn page_size() -> usize {
+    4096
+}
@@ -0,0 +1,27 @@
+extern crate psm;
+
+// The constants are usable in const contexts.
+const CAPABILITIES: [bool; 4] = [
+    psm::CAN_SWITCH,
+    psm::CAN_SWITCH_CONTEXT,
+    psm::HAS_STACK_INFO,
+    psm::HAS_FRAME_INFO,
+];
+
+#[test]
+fn constants_agree_with_macros() {
+    assert_eq!(
+        CAPABILITIES,
+        [
+            psm::psm_stack_manipulation! { yes { true } no { false } },
+            psm::psm_context_switch! { yes { true } no { false } },
+            psm::psm_stack_information! { yes { true } no { false } },
+            psm::psm_frame_information! { yes { true } no { false } },
+        ]
+    );
+}
+
+#[test]
+fn unwinding_requires_switching() {
+    assert!(!psm::CAN_UNWIND || psm::CAN_SWITCH);
+}
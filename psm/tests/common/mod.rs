@@ -0,0 +1,32 @@
+//! Helpers shared by the integration tests.
+// Not every test uses every helper, or any of them on every target.
+#![allow(dead_code)]
+
+use std::alloc;
+
+/// A stack for the tests to switch to, allocated from the global allocator and aligned to the page
+/// size.
+pub struct Stack {
+    base: *mut u8,
+    layout: alloc::Layout,
+}
+
+impl Stack {
+    pub fn new(size: usize) -> Stack {
+        let layout = alloc::Layout::from_size_align(size, 4096).unwrap();
+        let base = unsafe { alloc::alloc(layout) };
+        assert!(!base.is_null(), "allocations must succeed!");
+        Stack { base, layout }
+    }
+
+    /// The low address of the stack.
+    pub fn base(&self) -> *mut u8 {
+        self.base
+    }
+}
+
+impl Drop for Stack {
+    fn drop(&mut self) {
+        unsafe { alloc::dealloc(self.base, self.layout) };
+    }
+}
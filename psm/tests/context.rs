@@ -0,0 +1,210 @@
+extern crate psm;
+
+mod common;
+
+psm::psm_context_switch! {
+    yes {
+        use common::Stack;
+        use std::hint::black_box;
+
+        const STACK_SIZE: usize = 256 * 1024;
+
+        /// State shared between the main stack and a context, passed as the entry argument.
+        struct PingPong {
+            main: psm::Context,
+            other: psm::Context,
+            value: u64,
+            stack_pointer: *mut u8,
+        }
+
+        fn ping_pong(arg: usize) -> ! {
+            let state = arg as *mut PingPong;
+            unsafe {
+                (*state).stack_pointer = psm::stack_pointer();
+                loop {
+                    (*state).value = (*state).value * 2 + 1;
+                    psm::swap_context(&mut (*state).other, &(*state).main);
+                }
+            }
+        }
+
+        #[test]
+        fn runs_entry_on_new_stack() {
+            let stack = Stack::new(STACK_SIZE);
+            let mut state = PingPong {
+                main: psm::Context::new(),
+                other: psm::Context::new(),
+                value: 0,
+                stack_pointer: std::ptr::null_mut(),
+            };
+            unsafe {
+                let state = &mut state as *mut PingPong;
+                (*state).other = psm::init_context(stack.base(), STACK_SIZE, ping_pong, state as usize);
+                psm::swap_context(&mut (*state).main, &(*state).other);
+            }
+            assert_eq!(state.value, 1);
+            let sp = state.stack_pointer as usize;
+            assert!(sp > stack.base() as usize && sp < stack.base() as usize + STACK_SIZE);
+        }
+
+        #[test]
+        fn switches_back_and_forth() {
+            let stack = Stack::new(STACK_SIZE);
+            let mut state = PingPong {
+                main: psm::Context::new(),
+                other: psm::Context::new(),
+                value: 0,
+                stack_pointer: std::ptr::null_mut(),
+            };
+            unsafe {
+                let state = &mut state as *mut PingPong;
+                (*state).other = psm::init_context(stack.base(), STACK_SIZE, ping_pong, state as usize);
+                for i in 1..=10 {
+                    psm::swap_context(&mut (*state).main, &(*state).other);
+                    assert_eq!((*state).value, (1 << i) - 1);
+                }
+            }
+        }
+
+        struct Clobber {
+            main: psm::Context,
+            other: psm::Context,
+        }
+
+        /// Keeps a lot of values live in registers and clobbers as many of them as it can before
+        /// switching back.
+        fn clobber(arg: usize) -> ! {
+            let state = arg as *mut Clobber;
+            let mut ints = [0u64; 16];
+            let mut floats = [0f64; 16];
+            loop {
+                for (i, (int, float)) in ints.iter_mut().zip(floats.iter_mut()).enumerate() {
+                    *int = black_box(!(i as u64));
+                    *float = black_box(-(i as f64));
+                }
+                black_box((&ints, &floats));
+                unsafe {
+                    clobber_registers();
+                    psm::swap_context(&mut (*state).other, &(*state).main);
+                }
+            }
+        }
+
+        /// Overwrites the callee-saved registers that inline assembly is allowed to name, so that
+        /// any register `swap_context` fails to restore ends up with a bogus value.
+        #[inline(always)]
+        unsafe fn clobber_registers() {
+            #[cfg(target_arch = "x86_64")]
+            std::arch::asm!(
+                "mov r12, -1", "mov r13, -1", "mov r14, -1", "mov r15, -1",
+                out("r12") _, out("r13") _, out("r14") _, out("r15") _,
+            );
+            #[cfg(target_arch = "x86")]
+            std::arch::asm!(
+                "mov edi, -1",
+                out("edi") _,
+            );
+            #[cfg(target_arch = "aarch64")]
+            std::arch::asm!(
+                "mov x20, #-1", "mov x21, #-1", "mov x22, #-1", "mov x23, #-1", "mov x24, #-1",
+                "mov x25, #-1", "mov x26, #-1", "mov x27, #-1", "mov x28, #-1",
+                "fmov d8, #-1.0", "fmov d9, #-1.0", "fmov d10, #-1.0", "fmov d11, #-1.0",
+                "fmov d12, #-1.0", "fmov d13, #-1.0", "fmov d14, #-1.0", "fmov d15, #-1.0",
+                out("x20") _, out("x21") _, out("x22") _, out("x23") _, out("x24") _,
+                out("x25") _, out("x26") _, out("x27") _, out("x28") _,
+                out("d8") _, out("d9") _, out("d10") _, out("d11") _,
+                out("d12") _, out("d13") _, out("d14") _, out("d15") _,
+            );
+            #[cfg(target_arch = "riscv64")]
+            std::arch::asm!(
+                "li s2, -1", "li s3, -1", "li s4, -1", "li s5, -1", "li s6, -1",
+                "li s7, -1", "li s8, -1", "li s9, -1", "li s10, -1", "li s11, -1",
+                out("s2") _, out("s3") _, out("s4") _, out("s5") _, out("s6") _,
+                out("s7") _, out("s8") _, out("s9") _, out("s10") _, out("s11") _,
+            );
+        }
+
+        #[inline(never)]
+        fn check_registers(state: *mut Clobber, seed: u64) {
+            // Few enough values that they all fit into callee-saved registers on every target.
+            let (a, b, c, d) = (
+                black_box(seed),
+                black_box(seed + 1),
+                black_box(seed + 2),
+                black_box(seed + 3),
+            );
+            let (x, y, z, w) = (
+                black_box(seed as f64 + 0.5),
+                black_box(seed as f64 + 1.5),
+                black_box(seed as f64 + 2.5),
+                black_box(seed as f64 + 3.5),
+            );
+            unsafe { psm::swap_context(&mut (*state).main, &(*state).other) };
+            assert_eq!((a, b, c, d), (seed, seed + 1, seed + 2, seed + 3));
+            assert_eq!(
+                (x, y, z, w),
+                (seed as f64 + 0.5, seed as f64 + 1.5, seed as f64 + 2.5, seed as f64 + 3.5)
+            );
+        }
+
+        #[test]
+        fn preserves_callee_saved_registers() {
+            let stack = Stack::new(STACK_SIZE);
+            let mut state = Clobber {
+                main: psm::Context::new(),
+                other: psm::Context::new(),
+            };
+            let state = &mut state as *mut Clobber;
+            unsafe {
+                (*state).other = psm::init_context(stack.base(), STACK_SIZE, clobber, state as usize);
+            }
+            for seed in 0..100 {
+                check_registers(state, seed);
+            }
+        }
+
+        struct Pair {
+            main: psm::Context,
+            contexts: [psm::Context; 2],
+            log: Vec<usize>,
+        }
+
+        fn relay(arg: usize) -> ! {
+            let (state, id) = (arg & !1, arg & 1);
+            let state = state as *mut Pair;
+            unsafe {
+                for round in 0..3 {
+                    (*state).log.push(id * 10 + round);
+                    let (from, to): (*mut psm::Context, *const psm::Context) = if id == 0 {
+                        (&mut (*state).contexts[0], &(*state).contexts[1])
+                    } else {
+                        (&mut (*state).contexts[1], &(*state).contexts[0])
+                    };
+                    psm::swap_context(from, to);
+                }
+                psm::swap_context(&mut (*state).contexts[id], &(*state).main);
+            }
+            unreachable!();
+        }
+
+        #[test]
+        fn switches_between_two_contexts() {
+            let stacks = [Stack::new(STACK_SIZE), Stack::new(STACK_SIZE)];
+            let mut state = Box::new(Pair {
+                main: psm::Context::new(),
+                contexts: [psm::Context::new(), psm::Context::new()],
+                log: Vec::new(),
+            });
+            let state = &mut *state as *mut Pair;
+            unsafe {
+                for (id, stack) in stacks.iter().enumerate() {
+                    (*state).contexts[id] =
+                        psm::init_context(stack.base(), STACK_SIZE, relay, state as usize | id);
+                }
+                psm::swap_context(&mut (*state).main, &(*state).contexts[0]);
+                assert_eq!((*state).log, [0, 10, 1, 11, 2, 12]);
+            }
+        }
+    }
+    no {}
+}
@@ -0,0 +1,73 @@
+//! These tests need the frame records to be maintained, so they are ignored unless the code is
+//! built with `-C force-frame-pointers=yes` or for a target that keeps frame pointers by default.
+extern crate psm;
+
+psm::psm_frame_information! {
+    yes {
+        use std::hint::black_box;
+
+        /// The frame pointer saved in the frame record `fp` points to.
+        unsafe fn previous_frame_pointer(fp: *mut u8) -> *mut u8 {
+            let offset = if cfg!(any(
+                target_arch = "x86",
+                target_arch = "x86_64",
+                target_arch = "aarch64",
+                target_arch = "arm64ec",
+            )) {
+                0
+            } else {
+                -2 * std::mem::size_of::<usize>() as isize
+            };
+            *(fp.offset(offset) as *const *mut u8)
+        }
+
+        /// The frame pointer and return address of this function, checking that its frame record
+        /// links to the frame of its caller, whose frame pointer is `caller_fp`. Only memory known
+        /// to be on the current stack is inspected.
+        #[inline(never)]
+        fn callee(caller_fp: *mut u8) -> (usize, usize) {
+            let fp = psm::frame_pointer();
+            let sp = psm::stack_pointer();
+            assert!(
+                fp >= sp && fp < caller_fp && (fp as usize - sp as usize) < 4096,
+                "fp = {:p} is not in the frame between sp = {:p} and the caller at {:p}",
+                fp,
+                sp,
+                caller_fp
+            );
+            unsafe {
+                assert_eq!(previous_frame_pointer(fp), caller_fp);
+                (fp as usize, psm::return_address() as usize)
+            }
+        }
+
+        #[inline(never)]
+        fn caller() -> (usize, usize) {
+            // `black_box` keeps the call from being a tail call, so that it returns into this
+            // function.
+            black_box(callee(psm::frame_pointer()))
+        }
+
+        #[test]
+        #[cfg_attr(not(frame_pointers), ignore)]
+        fn return_address_is_in_the_caller() {
+            let (_, return_address) = caller();
+            let start = caller as fn() -> (usize, usize) as usize;
+            assert!(
+                return_address > start && return_address - start < 4096,
+                "return address {:#x} is not within `caller` at {:#x}",
+                return_address,
+                start
+            );
+        }
+
+        #[test]
+        #[cfg_attr(not(frame_pointers), ignore)]
+        fn frame_pointer_is_on_the_stack() {
+            let (fp, _) = caller();
+            let sp = psm::stack_pointer() as usize;
+            assert!(fp < sp, "fp = {:#x}, sp = {:#x}", fp, sp);
+        }
+    }
+    no {}
+}
@@ -0,0 +1,50 @@
+//! Checks the GNU property note of the assembly, without which the linker disables control-flow
+//! protection for any binary linking it. The naked functions are compiled by rustc instead, so
+//! this only tests something with `PSM_NO_NAKED=1` on the targets that have them.
+#![cfg(all(target_os = "linux", not(miri)))]
+
+use std::path::Path;
+use std::process::Command;
+
+/// The notes of the objects in the archive built from the assembly.
+#[allow(dead_code)]
+fn assembly_notes() -> String {
+    let archive = Path::new(env!("OUT_DIR")).join("libpsm_s.a");
+    assert!(archive.exists(), "{} was not built", archive.display());
+    let output = Command::new("readelf")
+        .args(["--notes", "--wide"])
+        .arg(&archive)
+        .output()
+        .unwrap_or_else(|e| panic!("failed to run readelf: {}", e));
+    assert!(
+        output.status.success(),
+        "readelf failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[cfg(target_arch = "x86_64")]
+#[test]
+#[cfg_attr(not(link_asm), ignore)]
+fn x86_64_assembly_supports_ibt() {
+    let notes = assembly_notes();
+    // Switching stacks is not compatible with shadow stacks, so only IBT may be advertised.
+    assert!(
+        notes.contains("x86 feature: IBT\n"),
+        "the assembly is not marked as supporting IBT only:\n{}",
+        notes
+    );
+}
+
+#[cfg(target_arch = "aarch64")]
+#[test]
+#[cfg_attr(not(link_asm), ignore)]
+fn aarch64_assembly_supports_branch_protection() {
+    let notes = assembly_notes();
+    assert!(
+        notes.contains("AArch64 feature: BTI, PAC"),
+        "the assembly is not marked as supporting BTI and PAC:\n{}",
+        notes
+    );
+}
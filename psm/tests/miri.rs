@@ -0,0 +1,47 @@
+//! The emulation of the stack manipulation routines under Miri.
+#![cfg(miri)]
+
+extern crate psm;
+
+mod common;
+
+use common::Stack;
+
+const STACK_SIZE: usize = 4096;
+
+#[test]
+fn capabilities() {
+    assert!(psm::CAN_SWITCH);
+    assert_eq!(psm::CAN_UNWIND, !cfg!(windows));
+    assert!(!psm::CAN_SWITCH_CONTEXT);
+    assert!(psm::HAS_STACK_INFO);
+    assert!(!psm::HAS_FRAME_INFO);
+    assert_eq!(psm::StackDirection::new(), psm::StackDirection::Descending);
+}
+
+#[test]
+fn on_stack_runs_callback() {
+    let stack = Stack::new(STACK_SIZE);
+    let mut value = 0;
+    let result = unsafe {
+        psm::on_stack(stack.base(), STACK_SIZE, || {
+            value += 1;
+            value * 2
+        })
+    };
+    assert_eq!((value, result), (1, 2));
+}
+
+#[test]
+#[should_panic(expected = "psm::on_stack: the stack")]
+fn on_stack_misaligned_base() {
+    let stack = Stack::new(STACK_SIZE);
+    unsafe { psm::on_stack(stack.base().add(8), STACK_SIZE - 16, || ()) };
+}
+
+#[test]
+#[should_panic(expected = "psm::on_stack: the stack")]
+fn on_stack_misaligned_size() {
+    let stack = Stack::new(STACK_SIZE);
+    unsafe { psm::on_stack(stack.base(), STACK_SIZE - 8, || ()) };
+}
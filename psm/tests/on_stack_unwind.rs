@@ -0,0 +1,73 @@
+// `on_stack_unwind` is only available where the stack switching routine has correct unwind
+// information.
+#![cfg(unwindable_stack)]
+
+extern crate psm;
+
+mod common;
+
+use common::Stack;
+use std::cell::Cell;
+use std::panic;
+
+// Generating backtraces (because of RUST_BACKTRACE) creates a few quite large frames.
+const STACK_SIZE: usize = 4096 * 10;
+const CHAIN_DEPTH: usize = 16;
+
+thread_local! {
+    static DROPPED: Cell<usize> = const { Cell::new(0) };
+}
+
+struct CountDrop;
+
+impl Drop for CountDrop {
+    fn drop(&mut self) {
+        DROPPED.with(|d| d.set(d.get() + 1));
+    }
+}
+
+fn panic_chain(depth: usize) {
+    if depth == 0 {
+        panic::panic_any(depth);
+    }
+    let stack = Stack::new(STACK_SIZE);
+    unsafe {
+        psm::on_stack_unwind(stack.base(), STACK_SIZE, || {
+            let _guard = CountDrop;
+            panic_chain(depth - 1);
+        });
+    }
+}
+
+#[test]
+fn unwinds_through_nested_stacks() {
+    DROPPED.with(|d| d.set(0));
+    let payload = panic::catch_unwind(|| panic_chain(CHAIN_DEPTH)).unwrap_err();
+    assert_eq!(payload.downcast_ref::<usize>(), Some(&0));
+    assert_eq!(DROPPED.with(Cell::get), CHAIN_DEPTH);
+}
+
+#[test]
+fn catches_on_intermediate_stack() {
+    let stack = Stack::new(STACK_SIZE);
+    let caught = unsafe {
+        psm::on_stack_unwind(stack.base(), STACK_SIZE, || {
+            panic::catch_unwind(|| panic_chain(CHAIN_DEPTH / 2)).is_err()
+        })
+    };
+    assert!(caught);
+}
+
+#[test]
+fn panics_repeatedly_on_same_stack() {
+    let stack = Stack::new(STACK_SIZE);
+    for i in 0..8usize {
+        let payload = panic::catch_unwind(|| unsafe {
+            psm::on_stack_unwind(stack.base(), STACK_SIZE, || panic::panic_any(i))
+        })
+        .unwrap_err();
+        assert_eq!(payload.downcast_ref::<usize>(), Some(&i));
+    }
+    let result = unsafe { psm::on_stack_unwind(stack.base(), STACK_SIZE, || 4 + 4) };
+    assert_eq!(result, 8);
+}
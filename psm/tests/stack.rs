@@ -0,0 +1,109 @@
+extern crate psm;
+
+#[cfg(switchable_stack)]
+use std::panic;
+
+#[test]
+fn size_is_rounded_up_and_writable() {
+    for size in [0, 1, 4095, 4096, 4097, 64 * 1024] {
+        let stack = psm::Stack::new(size);
+        assert!(stack.size() >= size);
+        assert_eq!(stack.size() % 4096, 0);
+        assert_eq!(stack.base() as usize % 4096, 0);
+        for offset in 0..stack.size() {
+            unsafe { stack.base().add(offset).write_volatile(0xAB) };
+        }
+    }
+}
+
+psm::psm_stack_manipulation! {
+    yes {
+        // The safe `on_stack` is only available on the targets that get guard pages.
+        #[cfg(not(any(unix, miri)))]
+        trait OnStack {
+            fn on_stack<R, F: FnOnce() -> R>(&mut self, callback: F) -> R;
+        }
+
+        #[cfg(not(any(unix, miri)))]
+        impl OnStack for psm::Stack {
+            fn on_stack<R, F: FnOnce() -> R>(&mut self, callback: F) -> R {
+                unsafe { self.on_stack_unchecked(callback) }
+            }
+        }
+
+        #[test]
+        #[cfg_attr(miri, ignore)] // The callback runs on the current stack under Miri
+        fn runs_on_stack() {
+            let mut stack = psm::Stack::new(64 * 1024);
+            let (base, size) = (stack.base() as usize, stack.size());
+            let sp = stack.on_stack(psm::stack_pointer) as usize;
+            assert!(sp > base && sp <= base + size);
+        }
+
+        #[test]
+        fn resumes_panic_on_original_stack() {
+            let mut stack = psm::Stack::new(64 * 1024);
+            let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+                stack.on_stack(|| panic::panic_any(42u32));
+            }));
+            let payload = result.unwrap_err();
+            assert_eq!(payload.downcast_ref::<u32>(), Some(&42));
+            // The stack is still usable after a panic.
+            assert_eq!(stack.on_stack(|| 4 + 4), 8);
+        }
+
+        #[test]
+        fn nested_stacks() {
+            fn recurse(depth: usize) -> usize {
+                if depth == 0 {
+                    return 0;
+                }
+                let mut stack = psm::Stack::new(64 * 1024);
+                stack.on_stack(|| recurse(depth - 1) + 1)
+            }
+            assert_eq!(recurse(16), 16);
+        }
+
+        #[test]
+        fn moves_between_threads() {
+            let mut stack = psm::Stack::new(64 * 1024);
+            assert_eq!(stack.on_stack(|| 1), 1);
+            let mut stack = std::thread::spawn(move || {
+                assert_eq!(stack.on_stack(|| 2), 2);
+                stack
+            })
+            .join()
+            .unwrap();
+            assert_eq!(stack.on_stack(|| 3), 3);
+        }
+
+        // `on_stack` is safe only because the pages around the stack are inaccessible.
+        #[cfg(target_os = "linux")]
+        #[test]
+        #[cfg_attr(miri, ignore)] // Miri cannot read `/proc`
+        fn on_stack_is_guarded() {
+            fn protection(address: usize) -> String {
+                let maps = std::fs::read_to_string("/proc/self/maps").unwrap();
+                for line in maps.lines() {
+                    let mut fields = line.split_whitespace();
+                    let range = fields.next().unwrap();
+                    let (start, end) = range.split_once('-').unwrap();
+                    let start = usize::from_str_radix(start, 16).unwrap();
+                    let end = usize::from_str_radix(end, 16).unwrap();
+                    if start <= address && address < end {
+                        return fields.next().unwrap().to_owned();
+                    }
+                }
+                panic!("{:#x} is not mapped", address);
+            }
+
+            let mut stack = psm::Stack::new(64 * 1024);
+            assert_eq!(stack.on_stack(|| 4 + 4), 8);
+            let (base, size) = (stack.base() as usize, stack.size());
+            assert_eq!(protection(base - 1), "---p");
+            assert_eq!(protection(base), "rw-p");
+            assert_eq!(protection(base + size), "---p");
+        }
+    }
+    no {}
+}
@@ -1,3 +1,5 @@
+#![cfg(asm)]
+
 extern crate psm;
 
 #[test]
@@ -1,3 +1,5 @@
+#![cfg(asm)]
+
 extern crate psm;
 
 #[inline(never)]
@@ -24,6 +26,7 @@ fn test_direction(previous_sp: *mut u8) {
 }
 
 #[test]
+#[cfg_attr(miri, ignore)] // Miri does not lay out stack frames in memory
 fn direction_right() {
     test_direction(psm::stack_pointer());
 }
@@ -0,0 +1,50 @@
+extern crate psm;
+
+#[cfg(asm)]
+use std::hint::black_box;
+
+/// The stack pointer is the caller's, so the locals of the caller are on the used side of it, and
+/// not too far away.
+#[cfg(asm)]
+#[test]
+#[cfg_attr(miri, ignore)] // The stack pointer is only approximated under Miri
+fn locals_of_the_caller_are_within_the_used_stack() {
+    let local = black_box([0u8; 64]);
+    let sp = psm::stack_pointer() as usize;
+    let local = black_box(&local).as_ptr() as usize;
+    match psm::StackDirection::new() {
+        psm::StackDirection::Ascending => {
+            assert!(
+                local < sp && sp - local < 4096,
+                "sp = {:#x}, local = {:#x}",
+                sp,
+                local
+            );
+        }
+        psm::StackDirection::Descending => {
+            assert!(
+                local >= sp && local - sp < 4096,
+                "sp = {:#x}, local = {:#x}",
+                sp,
+                local
+            );
+        }
+    }
+}
+
+psm::psm_stack_manipulation! {
+    yes {
+        #[test]
+        #[cfg_attr(miri, ignore)] // The callback runs on the current stack under Miri
+        fn stack_pointer_on_new_stack() {
+            const STACK_SIZE: usize = 64 * 1024;
+            let mut stack = vec![0u128; STACK_SIZE / 16];
+            let base = stack.as_mut_ptr() as usize;
+            let sp = unsafe {
+                psm::on_stack(base as *mut u8, STACK_SIZE, || psm::stack_pointer() as usize)
+            };
+            assert!(sp > base && sp < base + STACK_SIZE, "sp = {:#x}, base = {:#x}", sp, base);
+        }
+    }
+    no {}
+}
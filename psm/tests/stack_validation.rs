@@ -0,0 +1,96 @@
+//! The stacks passed to `on_stack` and similar functions are checked when debug assertions are
+//! enabled, before switching to them.
+extern crate psm;
+
+mod common;
+
+// Used by the tests for both stack switching and context switching.
+#[allow(dead_code)]
+const STACK_SIZE: usize = 64 * 1024;
+
+#[test]
+fn constants() {
+    assert!(psm::STACK_ALIGNMENT.is_power_of_two());
+    assert!(psm::MIN_STACK_SIZE.is_multiple_of(psm::STACK_ALIGNMENT));
+    // Page aligned stacks are always aligned well enough.
+    assert!(psm::STACK_ALIGNMENT <= 4096);
+}
+
+psm::psm_stack_manipulation! {
+    yes {
+        use common::Stack;
+
+        #[test]
+        fn aligned_stack_is_accepted() {
+            let stack = Stack::new(STACK_SIZE);
+            let base = unsafe { stack.base().add(psm::STACK_ALIGNMENT) };
+            let size = STACK_SIZE - 2 * psm::STACK_ALIGNMENT;
+            assert_eq!(unsafe { psm::on_stack(base, size, || 4 + 4) }, 8);
+            assert_eq!(unsafe { psm::on_stack(stack.base(), psm::MIN_STACK_SIZE, || 4 + 4) }, 8);
+        }
+
+        #[test]
+        #[cfg_attr(not(debug_assertions), ignore)]
+        #[should_panic(expected = "psm::on_stack: the stack base")]
+        fn on_stack_misaligned_base() {
+            let stack = Stack::new(STACK_SIZE);
+            unsafe { psm::on_stack(stack.base().add(1), STACK_SIZE - psm::STACK_ALIGNMENT, || ()) };
+        }
+
+        #[test]
+        #[cfg_attr(not(debug_assertions), ignore)]
+        #[should_panic(expected = "is not a multiple of `STACK_ALIGNMENT`")]
+        fn on_stack_misaligned_size() {
+            let stack = Stack::new(STACK_SIZE);
+            unsafe { psm::on_stack(stack.base(), STACK_SIZE - 4, || ()) };
+        }
+
+        #[test]
+        #[cfg_attr(not(debug_assertions), ignore)]
+        #[should_panic(expected = "overflows `isize`")]
+        fn on_stack_size_overflows_isize() {
+            let stack = Stack::new(STACK_SIZE);
+            unsafe { psm::on_stack(stack.base(), isize::MAX as usize + 1, || ()) };
+        }
+
+        #[test]
+        #[cfg(unwindable_stack)]
+        #[cfg_attr(not(debug_assertions), ignore)]
+        #[should_panic(expected = "psm::on_stack_unwind: the stack base")]
+        fn on_stack_unwind_misaligned_base() {
+            let stack = Stack::new(STACK_SIZE);
+            unsafe {
+                psm::on_stack_unwind(stack.base().add(8), STACK_SIZE - psm::STACK_ALIGNMENT, || ())
+            };
+        }
+
+        #[test]
+        #[cfg_attr(not(debug_assertions), ignore)]
+        #[should_panic(expected = "psm::replace_stack: the stack base")]
+        fn replace_stack_misaligned_base() {
+            let stack = Stack::new(STACK_SIZE);
+            unsafe {
+                psm::replace_stack(stack.base().add(8), STACK_SIZE - psm::STACK_ALIGNMENT, || {
+                    unreachable!("the stack is checked before switching to it")
+                })
+            };
+        }
+    }
+    no {}
+}
+
+psm::psm_context_switch! {
+    yes {
+        #[test]
+        #[cfg_attr(not(debug_assertions), ignore)]
+        #[should_panic(expected = "psm::init_context: the stack size")]
+        fn init_context_misaligned_size() {
+            fn entry(_: usize) -> ! {
+                unreachable!("the context is never switched to")
+            }
+            let mut stack = vec![0u128; STACK_SIZE / 16];
+            unsafe { psm::init_context(stack.as_mut_ptr() as *mut u8, STACK_SIZE - 8, entry, 0) };
+        }
+    }
+    no {}
+}
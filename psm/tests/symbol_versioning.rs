@@ -0,0 +1,19 @@
+// The assembly is neither preprocessed when targeting MSVC nor for WebAssembly, which uses a
+// prebuilt object file, so the routines keep their plain names there.
+#![cfg(all(link_asm, not(target_env = "msvc"), not(target_arch = "wasm32")))]
+
+extern crate psm;
+
+extern "C" {
+    #[link_name = concat!("rust_psm_stack_direction", env!("PSM_SYMBOL_SUFFIX"))]
+    fn versioned_stack_direction() -> u8;
+}
+
+#[test]
+fn routines_are_versioned() {
+    assert!(cfg!(versioned_symbols));
+    assert_eq!(
+        unsafe { versioned_stack_direction() },
+        psm::StackDirection::new() as u8
+    );
+}
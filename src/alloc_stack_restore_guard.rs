@@ -1,12 +1,15 @@
-use crate::{get_stack_limit, set_stack_limit};
+use crate::{get_stack_limit, set_stack_limit, StackLimit};
 
 pub struct StackRestoreGuard {
     new_stack: *mut u8,
     stack_bytes: usize,
-    old_stack_limit: Option<usize>,
+    old_stack_limit: Option<StackLimit>,
 }
 
-const ALIGNMENT: usize = 16;
+// The guard owns its memory, so it may be moved to another thread as long as it is not in use.
+unsafe impl Send for StackRestoreGuard {}
+
+const ALIGNMENT: usize = psm::STACK_ALIGNMENT;
 
 impl StackRestoreGuard {
     pub fn new(stack_bytes: usize) -> StackRestoreGuard {
@@ -25,13 +28,26 @@ impl StackRestoreGuard {
         StackRestoreGuard {
             new_stack: ptr,
             stack_bytes,
-            old_stack_limit: get_stack_limit(),
+            old_stack_limit: Some(get_stack_limit()),
         }
     }
 
     pub fn stack_area(&self) -> (*mut u8, usize) {
         (self.new_stack, self.stack_bytes)
     }
+
+    /// Remembers the current stack limit, so that it is restored by `restore_stack_limit` or when
+    /// the guard is dropped. Used when the stack is reused for another switch.
+    pub fn save_stack_limit(&mut self) {
+        self.old_stack_limit = Some(get_stack_limit());
+    }
+
+    /// Restores the stack limit remembered when the guard was created or by `save_stack_limit`.
+    pub fn restore_stack_limit(&mut self) {
+        if let Some(old_stack_limit) = self.old_stack_limit.take() {
+            set_stack_limit(old_stack_limit);
+        }
+    }
 }
 
 impl Drop for StackRestoreGuard {
@@ -42,6 +58,6 @@ impl Drop for StackRestoreGuard {
                 std::alloc::Layout::from_size_align_unchecked(self.stack_bytes, ALIGNMENT),
             );
         }
-        set_stack_limit(self.old_stack_limit);
+        self.restore_stack_limit();
     }
 }
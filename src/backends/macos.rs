@@ -1,6 +1,5 @@
-pub unsafe fn guess_os_stack_limit() -> Option<usize> {
-    Some(
-        libc::pthread_get_stackaddr_np(libc::pthread_self()) as usize
-            - libc::pthread_get_stacksize_np(libc::pthread_self()) as usize,
-    )
+pub unsafe fn guess_os_stack_bounds() -> Option<(usize, usize)> {
+    let top = libc::pthread_get_stackaddr_np(libc::pthread_self()) as usize;
+    let size = libc::pthread_get_stacksize_np(libc::pthread_self()) as usize;
+    Some((top - size, top))
 }
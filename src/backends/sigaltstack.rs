@@ -0,0 +1,16 @@
+/// Returns the limit of the alternate signal stack, if the thread is running on one.
+///
+/// `sigaltstack` is async-signal-safe, so this may be called from within signal handlers.
+pub fn alt_stack_limit() -> Option<usize> {
+    unsafe {
+        let mut stackinfo = std::mem::MaybeUninit::<libc::stack_t>::uninit();
+        if libc::sigaltstack(std::ptr::null(), stackinfo.as_mut_ptr()) != 0 {
+            return None;
+        }
+        let stackinfo = stackinfo.assume_init();
+        if stackinfo.ss_flags & libc::SS_ONSTACK == 0 {
+            return None;
+        }
+        Some(stackinfo.ss_sp as usize)
+    }
+}
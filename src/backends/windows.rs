@@ -27,7 +27,7 @@ unsafe extern "system" fn fiber_proc<F: FnOnce()>(data: *mut c_void) {
     // it.
     let data = &mut *(data as *mut FiberInfo<F>);
     let old_stack_limit = crate::get_stack_limit();
-    crate::set_stack_limit(guess_os_stack_limit());
+    crate::set_stack_limit(crate::StackLimit::from_bounds(guess_os_stack_bounds()));
     let callback = data.callback.as_ptr();
     data.panic = std::panic::catch_unwind(std::panic::AssertUnwindSafe(callback.read())).err();
 
@@ -123,9 +123,10 @@ fn get_thread_stack_guarantee() -> Option<usize> {
 }
 
 #[inline(always)]
-pub unsafe fn guess_os_stack_limit() -> Option<usize> {
+pub unsafe fn guess_os_stack_bounds() -> Option<(usize, usize)> {
     // Query the allocation which contains our stack pointer in order
-    // to discover the size of the stack
+    // to discover the size of the stack. The committed region containing the stack pointer
+    // extends all the way to the top of the stack.
     //
     // FIXME: we could read stack base from the TIB, specifically the 3rd element of it.
     type QueryT = windows_sys::Win32::System::Memory::MEMORY_BASIC_INFORMATION;
@@ -138,5 +139,9 @@ pub unsafe fn guess_os_stack_limit() -> Option<usize> {
     if res == 0 {
         return None;
     }
-    Some(mi.assume_init().AllocationBase as usize + get_thread_stack_guarantee()? + 0x1000)
+    let mi = mi.assume_init();
+    Some((
+        mi.AllocationBase as usize + get_thread_stack_guarantee()? + 0x1000,
+        mi.BaseAddress as usize + mi.RegionSize,
+    ))
 }
@@ -0,0 +1,345 @@
+//! Stackful coroutines running on stacks allocated by this library.
+
+use std::any::Any;
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem::ManuallyDrop;
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::sanitizers::Fiber;
+use crate::stack_restore_guard::StackRestoreGuard;
+use crate::{get_stack_limit, set_stack_limit, StackLimit};
+
+/// The value produced by [`Coroutine::resume`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CoroutineState<Yield, Return> {
+    /// The coroutine suspended itself with [`Yielder::yield_`].
+    Yielded(Yield),
+    /// The coroutine ran to completion.
+    Complete(Return),
+}
+
+/// A stackful coroutine.
+///
+/// The coroutine runs a closure on its own stack, which is allocated (with guard pages, where
+/// supported) when the coroutine is created. The closure may suspend itself at any depth of
+/// nested calls with [`Yielder::yield_`], and is continued by the next call to
+/// [`resume`](Coroutine::resume). While the coroutine runs, the limit used by [`maybe_grow`] and
+/// [`remaining_stack`] is that of the coroutine's stack.
+///
+/// If the closure panics, the panic is propagated to the caller of `resume`. If a coroutine that
+/// has not completed yet is dropped, its stack is unwound first, so that all values living on it
+/// are dropped properly. This is done by panicking out of the pending `yield_` call. If the closure
+/// catches that panic and yields again, dropping the coroutine panics and leaks its stack.
+///
+/// [`maybe_grow`]: crate::maybe_grow
+/// [`remaining_stack`]: crate::remaining_stack
+///
+/// # Examples
+///
+/// ```
+/// use stacker::{Coroutine, CoroutineState};
+///
+/// let mut coroutine = Coroutine::new(64 * 1024, |yielder| {
+///     yielder.yield_(1);
+///     yielder.yield_(2);
+///     "done"
+/// });
+/// assert_eq!(coroutine.resume(), CoroutineState::Yielded(1));
+/// assert_eq!(coroutine.resume(), CoroutineState::Yielded(2));
+/// assert_eq!(coroutine.resume(), CoroutineState::Complete("done"));
+/// ```
+pub struct Coroutine<'a, Yield, Return> {
+    inner: *mut Inner<'a, Yield, Return>,
+    // Only dropped once nothing is left on the stack, see `Drop`.
+    guard: ManuallyDrop<StackRestoreGuard>,
+    state: State,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Created,
+    Suspended,
+    Running,
+    Complete,
+}
+
+/// The state shared between the coroutine and its resumer. Only accessed through raw pointers,
+/// as either side of a switch may hold on to it.
+struct Shared<Yield> {
+    resumer: psm::Context,
+    coroutine: psm::Context,
+    coroutine_stack_limit: StackLimit,
+    yielded: Option<Yield>,
+    cancelled: bool,
+    resumer_fiber: Fiber,
+    coroutine_fiber: Fiber,
+}
+
+struct Inner<'a, Yield, Return> {
+    shared: Shared<Yield>,
+    callback: Option<Callback<'a, Yield, Return>>,
+    result: Option<Result<Return, Box<dyn Any + Send + 'static>>>,
+}
+
+type Callback<'a, Yield, Return> = Box<dyn FnOnce(&Yielder<Yield>) -> Return + 'a>;
+
+/// The payload used to unwind the stack of a coroutine that is dropped before completing.
+struct Cancelled;
+
+impl<'a, Yield, Return> Coroutine<'a, Yield, Return> {
+    /// Creates a new coroutine which will run `callback` on a stack of at least `stack_size`
+    /// bytes.
+    ///
+    /// The closure does not start running until the coroutine is resumed for the first time.
+    pub fn new<F>(stack_size: usize, callback: F) -> Coroutine<'a, Yield, Return>
+    where
+        F: FnOnce(&Yielder<Yield>) -> Return + 'a,
+    {
+        let mut guard = StackRestoreGuard::new(stack_size);
+        // The limit is saved and restored around each switch instead.
+        guard.restore_stack_limit();
+        let (stack_base, stack_size) = guard.stack_area();
+        let inner = Box::into_raw(Box::new(Inner {
+            shared: Shared {
+                resumer: psm::Context::new(),
+                coroutine: psm::Context::new(),
+                coroutine_stack_limit: StackLimit {
+                    limit: stack_base as usize,
+                    top: stack_base as usize + stack_size,
+                },
+                yielded: None,
+                cancelled: false,
+                resumer_fiber: Fiber::current(),
+                coroutine_fiber: Fiber::new(stack_base, stack_size),
+            },
+            callback: Some(Box::new(callback)),
+            result: None,
+        }));
+        unsafe {
+            // The stack is owned by `guard`, which lives as long as the coroutine can be resumed.
+            (*inner).shared.coroutine = psm::init_context(
+                stack_base,
+                stack_size,
+                coroutine_entry::<Yield, Return>,
+                inner as usize,
+            );
+        }
+        Coroutine {
+            inner,
+            guard: ManuallyDrop::new(guard),
+            state: State::Created,
+        }
+    }
+
+    /// Runs the coroutine until it yields a value or completes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the coroutine has already completed, or if the closure run by the coroutine
+    /// panicked.
+    pub fn resume(&mut self) -> CoroutineState<Yield, Return> {
+        match self.state {
+            State::Created | State::Suspended => {}
+            State::Running => panic!("coroutine resumed while it is running"),
+            State::Complete => panic!("coroutine resumed after completion"),
+        }
+        self.switch_in();
+        unsafe {
+            if let Some(result) = (*self.inner).result.take() {
+                self.state = State::Complete;
+                match result {
+                    Ok(value) => CoroutineState::Complete(value),
+                    Err(p) => panic::resume_unwind(p),
+                }
+            } else {
+                self.state = State::Suspended;
+                CoroutineState::Yielded((*self.inner).shared.yielded.take().unwrap())
+            }
+        }
+    }
+
+    /// Returns whether the coroutine has run to completion.
+    pub fn is_complete(&self) -> bool {
+        self.state == State::Complete
+    }
+
+    fn switch_in(&mut self) {
+        self.state = State::Running;
+        unsafe {
+            let shared = &mut (*self.inner).shared as *mut Shared<Yield>;
+            self.guard.save_stack_limit();
+            set_stack_limit((*shared).coroutine_stack_limit);
+            (*shared).resumer_fiber = Fiber::current();
+            (*shared)
+                .resumer_fiber
+                .start_switch(&(*shared).coroutine_fiber, false);
+            (*shared).coroutine_fiber.make_current();
+            psm::swap_context(&mut (*shared).resumer, &(*shared).coroutine);
+            (*shared)
+                .resumer_fiber
+                .finish_switch(&mut (*shared).coroutine_fiber);
+            // The coroutine may have yielded from a stack segment it has grown into.
+            (*shared).coroutine_stack_limit = get_stack_limit();
+            self.guard.restore_stack_limit();
+        }
+    }
+}
+
+impl<Yield, Return> Drop for Coroutine<'_, Yield, Return> {
+    fn drop(&mut self) {
+        if self.state == State::Suspended {
+            unsafe {
+                (*self.inner).shared.cancelled = true;
+            }
+            self.switch_in();
+            if unsafe { (*self.inner).result.is_none() } {
+                // The closure caught the panic unwinding its stack and yielded again. The frames
+                // left on the stack can never be dropped, so the stack and the state they refer to
+                // are leaked rather than freed under them.
+                panic!("coroutine yielded while it was being dropped");
+            }
+        }
+        // Either the callback has not been started yet, or it has run to completion by now. In
+        // both cases there is nothing left on the stack of the coroutine.
+        unsafe {
+            drop(Box::from_raw(self.inner));
+            ManuallyDrop::drop(&mut self.guard);
+        }
+    }
+}
+
+impl<Yield, Return> fmt::Debug for Coroutine<'_, Yield, Return> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Coroutine")
+            .field("complete", &self.is_complete())
+            .finish()
+    }
+}
+
+/// A handle that lets a running coroutine suspend itself.
+///
+/// A reference to the `Yielder` is passed to the closure run by a [`Coroutine`].
+pub struct Yielder<Yield> {
+    shared: *mut Shared<Yield>,
+    // The coroutine is tied to the thread it is resumed on.
+    _marker: PhantomData<*const ()>,
+}
+
+impl<Yield> Yielder<Yield> {
+    /// Suspends the coroutine, handing `value` to the caller of [`Coroutine::resume`].
+    ///
+    /// Returns once the coroutine is resumed again.
+    pub fn yield_(&self, value: Yield) {
+        unsafe {
+            let shared = self.shared;
+            (*shared).yielded = Some(value);
+            (*shared)
+                .coroutine_fiber
+                .start_switch(&(*shared).resumer_fiber, false);
+            (*shared).resumer_fiber.make_current();
+            psm::swap_context(&mut (*shared).coroutine, &(*shared).resumer);
+            (*shared)
+                .coroutine_fiber
+                .finish_switch(&mut (*shared).resumer_fiber);
+            if (*shared).cancelled {
+                panic::resume_unwind(Box::new(Cancelled));
+            }
+        }
+    }
+}
+
+impl<Yield> fmt::Debug for Yielder<Yield> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Yielder").finish()
+    }
+}
+
+/// An iterator over the values yielded by a closure running as a [`Coroutine`].
+///
+/// This makes it possible to turn a recursive traversal into an iterator, without having to
+/// rewrite it in terms of an explicit stack.
+///
+/// # Examples
+///
+/// ```
+/// enum Tree {
+///     Leaf(u32),
+///     Node(Box<Tree>, Box<Tree>),
+/// }
+///
+/// fn walk(tree: &Tree, yielder: &stacker::Yielder<u32>) {
+///     match tree {
+///         Tree::Leaf(v) => yielder.yield_(*v),
+///         Tree::Node(l, r) => {
+///             walk(l, yielder);
+///             walk(r, yielder);
+///         }
+///     }
+/// }
+///
+/// let tree = Tree::Node(Box::new(Tree::Leaf(1)), Box::new(Tree::Leaf(2)));
+/// let leaves = stacker::Generator::new(64 * 1024, |yielder| walk(&tree, yielder));
+/// assert_eq!(leaves.collect::<Vec<_>>(), [1, 2]);
+/// ```
+pub struct Generator<'a, T> {
+    coroutine: Coroutine<'a, T, ()>,
+}
+
+impl<'a, T> Generator<'a, T> {
+    /// Creates a generator which will run `callback` on a stack of at least `stack_size` bytes.
+    pub fn new<F>(stack_size: usize, callback: F) -> Generator<'a, T>
+    where
+        F: FnOnce(&Yielder<T>) + 'a,
+    {
+        Generator {
+            coroutine: Coroutine::new(stack_size, callback),
+        }
+    }
+}
+
+impl<T> Iterator for Generator<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.coroutine.is_complete() {
+            return None;
+        }
+        match self.coroutine.resume() {
+            CoroutineState::Yielded(value) => Some(value),
+            CoroutineState::Complete(()) => None,
+        }
+    }
+}
+
+impl<T> fmt::Debug for Generator<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Generator")
+            .field("coroutine", &self.coroutine)
+            .finish()
+    }
+}
+
+unsafe fn coroutine_entry<Yield, Return>(inner: usize) -> ! {
+    let inner = inner as *mut Inner<'_, Yield, Return>;
+    let shared = &mut (*inner).shared as *mut Shared<Yield>;
+    (*shared)
+        .coroutine_fiber
+        .finish_switch(&mut (*shared).resumer_fiber);
+    {
+        let yielder = Yielder {
+            shared: &mut (*inner).shared,
+            _marker: PhantomData,
+        };
+        let callback = (*inner).callback.take().unwrap();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| callback(&yielder)));
+        (*inner).result = Some(result);
+    }
+    // Leave the stack of the coroutine for good. Everything living on it has been dropped above.
+    (*shared)
+        .coroutine_fiber
+        .start_switch(&(*shared).resumer_fiber, true);
+    (*shared).resumer_fiber.make_current();
+    psm::swap_context(&mut (*shared).coroutine, &(*shared).resumer);
+    unreachable!("completed coroutine was resumed");
+}
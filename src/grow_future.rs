@@ -0,0 +1,109 @@
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use crate::{
+    _grow_on_segment, get_stack_limit, grow, has_remaining_stack, with_dyn_callback, Segment,
+};
+
+/// Grows the call stack if necessary while polling `future`.
+///
+/// This is the asynchronous counterpart of [`maybe_grow`](crate::maybe_grow): every call to
+/// `poll` on the returned future checks whether we're within `red_zone` bytes of the end of the
+/// stack, and if so polls `future` on a new stack of at least `stack_size` bytes.
+///
+/// # Examples
+///
+/// ```
+/// use std::future::Future;
+/// use std::pin::Pin;
+///
+/// fn sum(n: u64) -> Pin<Box<dyn Future<Output = u64>>> {
+///     Box::pin(stacker::maybe_grow_async(32 * 1024, 1024 * 1024, async move {
+///         if n == 0 { 0 } else { n + sum(n - 1).await }
+///     }))
+/// }
+/// ```
+pub fn maybe_grow_async<F: Future>(red_zone: usize, stack_size: usize, future: F) -> GrowFuture<F> {
+    GrowFuture::new(red_zone, stack_size, future)
+}
+
+/// A future which polls the wrapped future on a new stack when the current one runs low.
+///
+/// Created by [`maybe_grow_async`]. By default a new stack is allocated for every `poll` that
+/// needs one and is freed as soon as `poll` returns. With [`keep_segment`](Self::keep_segment)
+/// the stack is instead kept around and reused until the future is dropped, which avoids
+/// allocating a stack on every `poll` at the cost of holding on to the memory while the future
+/// is pending.
+///
+/// The stack is only ever used for the duration of a single `poll`, so the future may be moved
+/// between threads in between calls to `poll` as long as the wrapped future is `Send`.
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct GrowFuture<F> {
+    future: F,
+    red_zone: usize,
+    stack_size: usize,
+    keep_segment: bool,
+    segment: Option<Segment>,
+}
+
+impl<F: Future> GrowFuture<F> {
+    /// Wraps `future`, see [`maybe_grow_async`].
+    pub fn new(red_zone: usize, stack_size: usize, future: F) -> GrowFuture<F> {
+        GrowFuture {
+            future,
+            red_zone,
+            stack_size,
+            keep_segment: false,
+            segment: None,
+        }
+    }
+
+    /// Configures whether a stack allocated to poll the future is kept and reused for later calls
+    /// to `poll`, until the future is dropped.
+    pub fn keep_segment(mut self, keep_segment: bool) -> GrowFuture<F> {
+        self.keep_segment = keep_segment;
+        self
+    }
+}
+
+impl<F: Future> Future for GrowFuture<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        // Safety: `future` is structurally pinned, and is never moved out of `self`. None of the
+        // other fields are pinned.
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+        if has_remaining_stack(get_stack_limit(), this.red_zone) {
+            future.poll(cx)
+        } else if this.keep_segment {
+            grow_on_segment(&mut this.segment, this.stack_size, || future.poll(cx))
+        } else {
+            grow(this.stack_size, || future.poll(cx))
+        }
+    }
+}
+
+impl<F: fmt::Debug> fmt::Debug for GrowFuture<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GrowFuture")
+            .field("future", &self.future)
+            .field("red_zone", &self.red_zone)
+            .field("stack_size", &self.stack_size)
+            .field("keep_segment", &self.keep_segment)
+            .finish()
+    }
+}
+
+/// Like `grow`, but reuses the stack in `segment`, allocating it first if necessary.
+fn grow_on_segment<R, F: FnOnce() -> R>(
+    segment: &mut Option<Segment>,
+    stack_size: usize,
+    callback: F,
+) -> R {
+    with_dyn_callback(callback, |dyn_callback| {
+        _grow_on_segment(segment, stack_size, dyn_callback)
+    })
+}
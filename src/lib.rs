@@ -33,8 +33,13 @@ extern crate windows_sys;
 extern crate psm;
 
 mod backends;
+mod grow_future;
+
+pub use grow_future::{maybe_grow_async, GrowFuture};
 
 use std::cell::Cell;
+use std::fmt;
+use std::marker::PhantomData;
 
 /// Grows the call stack if necessary.
 ///
@@ -45,18 +50,31 @@ use std::cell::Cell;
 ///
 /// The closure `f` is guaranteed to run on a stack with at least `red_zone` bytes, and it will be
 /// run on the current stack if there's space available.
+///
+/// See [`remaining_stack`] for the restrictions on calling this from signal handlers.
 #[inline(always)]
 pub fn maybe_grow<R, F: FnOnce() -> R>(red_zone: usize, stack_size: usize, callback: F) -> R {
+    if has_remaining_stack(get_stack_limit(), red_zone) {
+        callback()
+    } else {
+        grow(stack_size, callback)
+    }
+}
+
+#[inline(always)]
+fn has_remaining_stack(stack_limit: StackLimit, red_zone: usize) -> bool {
+    let current_ptr = current_stack_ptr();
+    // The common case of having plenty of stack comes down to comparing the stack pointer against
+    // the bounds stored in `STACK_LIMIT`. The sentinel values never pass this check, so there is
+    // no need to check whether the limit is known first.
+    if current_ptr >= stack_limit.limit.saturating_add(red_zone) && current_ptr < stack_limit.top {
+        return true;
+    }
     // if we can't guess the remaining stack (unsupported on some platforms) we immediately grow
     // the stack and then cache the new stack size (which we do know now because we allocated it.
-    let enough_space = match remaining_stack() {
+    match remaining_stack_slow(current_ptr) {
         Some(remaining) => remaining >= red_zone,
         None => false,
-    };
-    if enough_space {
-        callback()
-    } else {
-        grow(stack_size, callback)
     }
 }
 
@@ -64,6 +82,11 @@ pub fn maybe_grow<R, F: FnOnce() -> R>(red_zone: usize, stack_size: usize, callb
 /// The closure will still be on the same thread as the caller of `grow`.
 /// This will allocate a new stack with at least `stack_size` bytes.
 pub fn grow<R, F: FnOnce() -> R>(stack_size: usize, callback: F) -> R {
+    with_dyn_callback(callback, |dyn_callback| _grow(stack_size, dyn_callback))
+}
+
+/// Calls `run` with `callback` as a `dyn FnMut`, and returns what `callback` returned.
+fn with_dyn_callback<R, F: FnOnce() -> R>(callback: F, run: impl FnOnce(&mut dyn FnMut())) -> R {
     // To avoid monomorphizing `_grow()` and everything it calls,
     // we convert the generic callback to a dynamic one.
     let mut opt_callback = Some(callback);
@@ -81,21 +104,251 @@ pub fn grow<R, F: FnOnce() -> R>(stack_size: usize, callback: F) -> R {
         *ret_ref = Some(taken_callback());
     };
 
-    _grow(stack_size, dyn_callback);
+    run(dyn_callback);
+    ret.unwrap()
+}
+
+/// Grows the call stack if necessary, emulating growth with a thread where needed.
+///
+/// This is the same as [`maybe_grow`], except that on targets where this library is unable to
+/// switch stacks (see [`capabilities`]) the closure is run on a new thread with a stack of at
+/// least `stack_size` bytes instead of on the current stack. The calling thread is blocked until
+/// the closure completes, and a panic in the closure is propagated to the caller.
+#[inline(always)]
+pub fn maybe_grow_send<R: Send, F: FnOnce() -> R + Send>(
+    red_zone: usize,
+    stack_size: usize,
+    callback: F,
+) -> R {
+    if has_remaining_stack(get_stack_limit(), red_zone) {
+        callback()
+    } else {
+        grow_send(stack_size, callback)
+    }
+}
+
+/// Always creates a new stack for the passed closure to run on, emulating growth with a thread
+/// where needed.
+///
+/// This is the same as [`grow`], except that on targets where this library is unable to switch
+/// stacks (see [`capabilities`]) the closure is run on a new thread with a stack of at least
+/// `stack_size` bytes instead of on the current stack.
+///
+/// # Panics
+///
+/// Panics if a thread is needed but cannot be spawned.
+pub fn grow_send<R: Send, F: FnOnce() -> R + Send>(stack_size: usize, callback: F) -> R {
+    // See `with_dyn_callback` for what is going on here.
+    let mut opt_callback = Some(callback);
+    let mut ret = None;
+    let ret_ref = &mut ret;
+    let dyn_callback: &mut (dyn FnMut() + Send) = &mut || {
+        let taken_callback = opt_callback.take().unwrap();
+        *ret_ref = Some(taken_callback());
+    };
+
+    _grow_send(stack_size, dyn_callback);
     ret.unwrap()
 }
 
+/// How a new stack is provided when growing the stack.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Growth {
+    /// A new stack is allocated and switched to on the current thread.
+    Native,
+    /// A new thread with a stack of the requested size is spawned and waited for. Growing the
+    /// stack panics if the thread cannot be spawned.
+    Thread,
+    /// The stack is not grown, the closure runs on the current stack.
+    NoOp,
+}
+
+/// The stack growth capabilities of this library on the current target.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub struct Capabilities {
+    /// How [`grow`] and [`maybe_grow`] provide a new stack.
+    pub grow: Growth,
+    /// How [`grow_send`] and [`maybe_grow_send`] provide a new stack.
+    pub grow_send: Growth,
+}
+
+/// Queries how this library is able to grow the stack on the current target.
+pub fn capabilities() -> Capabilities {
+    Capabilities {
+        grow: GROWTH,
+        grow_send: GROWTH_SEND,
+    }
+}
+
 /// Queries the amount of remaining stack as interpreted by this library.
 ///
 /// This function will return the amount of stack space left which will be used
 /// to determine whether a stack switch should be made or not.
+///
+/// When called from a signal handler running on an alternate signal stack (see `sigaltstack(2)`),
+/// the space remaining on the alternate stack is reported instead.
+///
+/// The bounds of the thread's stack are looked up on first use, which may lock and allocate
+/// (`pthread_getattr_np`, for example). Once they are known this function neither allocates nor
+/// locks, so it may only be used from a signal handler if [`init_stack_limit`] has been called on
+/// the thread beforehand.
+#[inline]
 pub fn remaining_stack() -> Option<usize> {
     let current_ptr = current_stack_ptr();
-    get_stack_limit().map(|limit| current_ptr.saturating_sub(limit))
+    let stack_limit = get_stack_limit();
+    if stack_limit.contains(current_ptr) {
+        Some(current_ptr - stack_limit.limit)
+    } else {
+        remaining_stack_slow(current_ptr)
+    }
+}
+
+#[cold]
+fn remaining_stack_slow(current_ptr: usize) -> Option<usize> {
+    // The bounds checked by the caller may be those of a `StackCtx` used on another stack than the
+    // one it was created on, so look at the stack the thread is running on now.
+    let stack_limit = match get_stack_limit() {
+        StackLimit::UNINIT => lookup_stack_limit(),
+        stack_limit => stack_limit,
+    };
+    if stack_limit.contains(current_ptr) {
+        return Some(current_ptr - stack_limit.limit);
+    }
+    if !stack_limit.is_known() {
+        return None;
+    }
+    // We are not running on the stack we know about. Most likely this is a signal handler running
+    // on an alternate signal stack, whose limit has nothing to do with the stack of the thread.
+    if let Some(limit) = backends::alt_stack_limit() {
+        return Some(current_ptr.saturating_sub(limit));
+    }
+    Some(current_ptr.saturating_sub(stack_limit.limit))
+}
+
+/// Looks up the bounds of the current thread's stack, unless that has been done already.
+///
+/// Otherwise this happens the first time [`remaining_stack`] or [`maybe_grow`] is called on the
+/// thread, and asking the OS for the bounds may lock and allocate. Calling this function on a
+/// thread before installing a signal handler that may run on it is what makes [`remaining_stack`]
+/// safe to call from that handler, including when it runs on an alternate signal stack.
+pub fn init_stack_limit() {
+    if get_stack_limit() == StackLimit::UNINIT {
+        lookup_stack_limit();
+    }
+}
+
+/// A token carrying the bounds of the stack it was created on.
+///
+/// Threading a `StackCtx` through a recursive algorithm makes the "is there enough stack left"
+/// check cheaper than with [`maybe_grow`], because the bounds of the stack do not need to be
+/// looked up in thread-local storage on every check. The thread-local state is only consulted
+/// when a new stack segment is actually switched to.
+///
+/// A token is meant to be used on the stack it was created for. Nothing stops a token from being
+/// moved into the closure of [`StackCtx::maybe_grow`] and used on the new stack, but then every
+/// check falls back to looking up the bounds of the current stack, just like [`maybe_grow`] does.
+/// Tokens cannot be sent to other threads.
+///
+/// # Examples
+///
+/// ```
+/// enum Tree {
+///     Leaf,
+///     Node(Box<Tree>, Box<Tree>),
+/// }
+///
+/// fn depth(tree: &Tree, cx: stacker::StackCtx) -> usize {
+///     match tree {
+///         Tree::Leaf => 0,
+///         Tree::Node(l, r) => cx.maybe_grow(32 * 1024, 1024 * 1024, |cx| {
+///             1 + depth(l, cx).max(depth(r, cx))
+///         }),
+///     }
+/// }
+///
+/// let tree = Tree::Node(Box::new(Tree::Leaf), Box::new(Tree::Leaf));
+/// assert_eq!(stacker::StackCtx::with(|cx| depth(&tree, cx)), 1);
+/// ```
+#[derive(Clone, Copy)]
+pub struct StackCtx<'a> {
+    stack_limit: StackLimit,
+    // Ties the token to the closure it was passed to and keeps it on its thread.
+    _marker: PhantomData<*const &'a ()>,
+}
+
+impl StackCtx<'_> {
+    /// Runs `callback` with a token for the stack of the current thread.
+    pub fn with<R, F: for<'a> FnOnce(StackCtx<'a>) -> R>(callback: F) -> R {
+        callback(StackCtx::current())
+    }
+
+    fn current() -> StackCtx<'static> {
+        let stack_limit = match get_stack_limit() {
+            StackLimit::UNINIT => lookup_stack_limit(),
+            stack_limit => stack_limit,
+        };
+        StackCtx {
+            stack_limit,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Grows the call stack if necessary.
+    ///
+    /// This is the same as [`maybe_grow`], but checks the stack pointer against the bounds
+    /// carried by this token. `callback` receives a token for the stack it runs on.
+    #[inline(always)]
+    pub fn maybe_grow<R, F: for<'a> FnOnce(StackCtx<'a>) -> R>(
+        self,
+        red_zone: usize,
+        stack_size: usize,
+        callback: F,
+    ) -> R {
+        if has_remaining_stack(self.stack_limit, red_zone) {
+            callback(self)
+        } else {
+            self.grow(stack_size, callback)
+        }
+    }
+
+    /// Always creates a new stack for the passed closure to run on.
+    ///
+    /// This is the same as [`grow`], but `callback` receives a token for the new stack.
+    pub fn grow<R, F: for<'a> FnOnce(StackCtx<'a>) -> R>(
+        self,
+        stack_size: usize,
+        callback: F,
+    ) -> R {
+        grow(stack_size, || callback(StackCtx::current()))
+    }
+
+    /// Queries the amount of remaining stack as interpreted by this library.
+    ///
+    /// See [`remaining_stack`].
+    #[inline]
+    pub fn remaining_stack(self) -> Option<usize> {
+        let current_ptr = current_stack_ptr();
+        if self.stack_limit.contains(current_ptr) {
+            Some(current_ptr - self.stack_limit.limit)
+        } else {
+            remaining_stack_slow(current_ptr)
+        }
+    }
+}
+
+impl fmt::Debug for StackCtx<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StackCtx")
+            .field("limit", &(self.stack_limit.limit as *const u8))
+            .field("top", &(self.stack_limit.top as *const u8))
+            .finish()
+    }
 }
 
 psm_stack_information!(
     yes {
+        #[inline(always)]
         fn current_stack_ptr() -> usize {
             psm::stack_pointer() as usize
         }
@@ -116,21 +369,91 @@ psm_stack_information!(
     }
 );
 
+/// What is known about the stack the current thread is running on.
+///
+/// The stack occupies the `[limit, top)` address range. `UNINIT` and `UNKNOWN` are sentinels which
+/// contain no addresses at all, while `UNBOUNDED` contains all of them.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct StackLimit {
+    limit: usize,
+    top: usize,
+}
+
+impl StackLimit {
+    /// The stack of the thread has not been queried from the OS yet.
+    const UNINIT: StackLimit = StackLimit {
+        limit: usize::MAX,
+        top: 0,
+    };
+    /// The limit of the stack could not be determined.
+    const UNKNOWN: StackLimit = StackLimit {
+        limit: usize::MAX,
+        top: usize::MAX,
+    };
+    /// `STACK_LIMIT` is inaccessible, and any stack is assumed to have enough room left.
+    const UNBOUNDED: StackLimit = StackLimit {
+        limit: 0,
+        top: usize::MAX,
+    };
+
+    fn from_bounds(bounds: Option<(usize, usize)>) -> StackLimit {
+        match bounds {
+            Some((limit, top)) => StackLimit { limit, top },
+            None => StackLimit::UNKNOWN,
+        }
+    }
+
+    #[inline(always)]
+    fn contains(self, ptr: usize) -> bool {
+        self.limit <= ptr && ptr < self.top
+    }
+
+    fn is_known(self) -> bool {
+        self.limit != usize::MAX
+    }
+}
+
 thread_local! {
-    static STACK_LIMIT: Cell<Option<usize>> = Cell::new(unsafe {
-        backends::guess_os_stack_limit()
-    })
+    // This is const-initialised and has no destructor, so accessing it never allocates or runs
+    // any lazy initialisation. The OS is only queried for the stack limit in
+    // `remaining_stack_slow`.
+    static STACK_LIMIT: Cell<StackLimit> = const { Cell::new(StackLimit::UNINIT) };
+}
+
+/// Looks up the limit of the thread's own stack and caches it in `STACK_LIMIT`.
+#[cold]
+fn lookup_stack_limit() -> StackLimit {
+    let stack_limit = StackLimit::from_bounds(unsafe { backends::guess_os_stack_bounds() });
+    set_stack_limit(stack_limit);
+    stack_limit
 }
 
+/// Returns the bounds of the stack the thread is running on, as far as they are known.
+///
+/// `STACK_LIMIT` has no destructor, so it stays accessible while thread-local destructors run and
+/// `try_with` does not fail on any current platform. The fallback to `UNBOUNDED` is only defensive:
+/// should the thread-local ever be inaccessible, `maybe_grow` runs the callback on the current
+/// stack rather than panicking or allocating a new stack on every call, and `set_stack_limit` drops
+/// the update.
 #[inline(always)]
-fn get_stack_limit() -> Option<usize> {
-    STACK_LIMIT.with(|s| s.get())
+fn get_stack_limit() -> StackLimit {
+    STACK_LIMIT
+        .try_with(|s| s.get())
+        .unwrap_or(StackLimit::UNBOUNDED)
 }
 
+/// Records the bounds of the stack the thread is switching to, see `get_stack_limit`.
 #[inline(always)]
 #[allow(unused)]
-fn set_stack_limit(l: Option<usize>) {
-    STACK_LIMIT.with(|s| s.set(l))
+fn set_stack_limit(l: StackLimit) {
+    let _ = STACK_LIMIT.try_with(|s| s.set(l));
+}
+
+// Under Miri psm only emulates `on_stack` by running the callback on the current stack, which is
+// of no use for growing it, so the fallbacks are used instead.
+#[cfg(miri)]
+macro_rules! psm_stack_manipulation {
+    (yes { $($yes: tt)* } no { $($no: tt)* }) => { $($no)* };
 }
 
 psm_stack_manipulation! {
@@ -145,27 +468,90 @@ psm_stack_manipulation! {
 
         use stack_restore_guard::StackRestoreGuard;
 
+        mod sanitizers;
+
+        #[cfg(feature = "valgrind")]
+        mod valgrind;
+
+        #[cfg(stacker_coroutines)]
+        mod coroutine;
+        #[cfg(stacker_coroutines)]
+        pub use coroutine::{Coroutine, CoroutineState, Generator, Yielder};
+
         fn _grow(requested_stack_size: usize, callback: &mut dyn FnMut()) {
-            // Other than that this code has no meaningful gotchas.
-            unsafe {
-                // We use a guard pattern to ensure we deallocate the allocated stack when we leave
-                // this function and also try to uphold various safety invariants required by `psm`
-                // (such as not unwinding from the callback we pass to it).
-                // `StackRestoreGuard` allocates a memory area with suitable size and alignment.
-                // It also sets up stack guards if supported on target.
-                let guard = StackRestoreGuard::new(requested_stack_size);
-                let (stack_base, allocated_stack_size) = guard.stack_area();
-                debug_assert!(allocated_stack_size >= requested_stack_size);
-                set_stack_limit(Some(stack_base as usize));
+            // We use a guard pattern to ensure we deallocate the allocated stack and restore the
+            // stack limit when we leave this function, even if the callback panics.
+            // `StackRestoreGuard` allocates a memory area with suitable size and alignment.
+            // It also sets up stack guards if supported on target.
+            let guard = StackRestoreGuard::new(requested_stack_size);
+            unsafe { run_on_guard(&guard, requested_stack_size, callback) };
+        }
+
+        /// Runs `callback` on the stack owned by `guard`, propagating any panic to the caller.
+        unsafe fn run_on_guard(
+            guard: &StackRestoreGuard,
+            requested_stack_size: usize,
+            callback: &mut dyn FnMut(),
+        ) {
+            let (stack_base, allocated_stack_size) = guard.stack_area();
+            debug_assert!(allocated_stack_size >= requested_stack_size);
+            set_stack_limit(StackLimit {
+                limit: stack_base as usize,
+                top: stack_base as usize + allocated_stack_size,
+            });
+            // Tell the sanitizers about the switch, and about the switch back once the callback
+            // returns or unwinds.
+            let mut switch = sanitizers::SegmentSwitch::new(stack_base, allocated_stack_size);
+            let switch_ref = &mut switch;
+            #[cfg(stacker_unwind)]
+            psm::on_stack_unwind(stack_base, allocated_stack_size, move || {
+                let _on_segment = switch_ref.enter();
+                callback()
+            });
+            // Without the unwind information for the stack switch a panic must not leave the
+            // callback of `psm::on_stack`, so it is caught there and resumed on this stack.
+            #[cfg(not(stacker_unwind))]
+            {
                 let panic = psm::on_stack(stack_base, allocated_stack_size, move || {
+                    let _on_segment = switch_ref.enter();
                     std::panic::catch_unwind(std::panic::AssertUnwindSafe(callback)).err()
                 });
-                drop(guard);
+                drop(switch);
                 if let Some(p) = panic {
                     std::panic::resume_unwind(p);
                 }
             }
         }
+
+        type Segment = StackRestoreGuard;
+
+        fn _grow_on_segment(
+            segment: &mut Option<Segment>,
+            requested_stack_size: usize,
+            callback: &mut dyn FnMut(),
+        ) {
+            let guard = segment.get_or_insert_with(|| StackRestoreGuard::new(requested_stack_size));
+            guard.save_stack_limit();
+            let guard = RestoreStackLimit(guard);
+            unsafe { run_on_guard(guard.0, requested_stack_size, callback) };
+        }
+
+        /// Restores the stack limit saved in the segment when dropped, even if the callback
+        /// panics, while keeping the segment around for reuse.
+        struct RestoreStackLimit<'a>(&'a mut Segment);
+
+        impl Drop for RestoreStackLimit<'_> {
+            fn drop(&mut self) {
+                self.0.restore_stack_limit();
+            }
+        }
+
+        fn _grow_send(stack_size: usize, callback: &mut (dyn FnMut() + Send)) {
+            _grow(stack_size, callback)
+        }
+
+        const GROWTH: Growth = Growth::Native;
+        const GROWTH_SEND: Growth = Growth::Native;
     }
 
     no {
@@ -176,5 +562,59 @@ psm_stack_manipulation! {
         }
         #[cfg(all(windows, not(miri)))]
         use backends::windows::_grow;
+
+        #[cfg(not(all(windows, not(miri))))]
+        fn _grow_send(stack_size: usize, callback: &mut (dyn FnMut() + Send)) {
+            // Space for the frames the thread runtime itself places on the stack of a new thread,
+            // before the closure is called.
+            const THREAD_OVERHEAD: usize = 64 * 1024;
+
+            let spawned = std::thread::scope(|scope| {
+                let thread = std::thread::Builder::new()
+                    .stack_size(stack_size.saturating_add(THREAD_OVERHEAD))
+                    .spawn_scoped(scope, || {
+                        if lookup_stack_limit() == StackLimit::UNKNOWN {
+                            // The OS cannot tell us where the stack of the thread is, but we know
+                            // how large we have asked it to be.
+                            let top = current_stack_ptr();
+                            set_stack_limit(StackLimit {
+                                limit: top.saturating_sub(stack_size),
+                                top,
+                            });
+                        }
+                        callback()
+                    });
+                thread.map(|thread| thread.join())
+            });
+            match spawned {
+                Ok(Ok(())) => {}
+                Ok(Err(p)) => std::panic::resume_unwind(p),
+                Err(e) => panic!("failed to spawn a thread to grow the stack on: {}", e),
+            }
+        }
+        #[cfg(all(windows, not(miri)))]
+        fn _grow_send(stack_size: usize, callback: &mut (dyn FnMut() + Send)) {
+            _grow(stack_size, callback)
+        }
+
+        // Segments are only reused when switching stacks on the current thread.
+        struct Segment;
+
+        fn _grow_on_segment(
+            _: &mut Option<Segment>,
+            requested_stack_size: usize,
+            callback: &mut dyn FnMut(),
+        ) {
+            _grow(requested_stack_size, callback)
+        }
+
+        #[cfg(not(all(windows, not(miri))))]
+        const GROWTH: Growth = Growth::NoOp;
+        #[cfg(not(all(windows, not(miri))))]
+        const GROWTH_SEND: Growth = Growth::Thread;
+        #[cfg(all(windows, not(miri)))]
+        const GROWTH: Growth = Growth::Native;
+        #[cfg(all(windows, not(miri)))]
+        const GROWTH_SEND: Growth = Growth::Native;
     }
 }
@@ -1,12 +1,17 @@
-use crate::{get_stack_limit, set_stack_limit};
+use crate::{get_stack_limit, set_stack_limit, StackLimit};
 
 pub struct StackRestoreGuard {
     mapping: *mut u8,
     size_with_guard: usize,
     page_size: usize,
-    old_stack_limit: Option<usize>,
+    old_stack_limit: Option<StackLimit>,
+    #[cfg(feature = "valgrind")]
+    valgrind_stack_id: usize,
 }
 
+// The guard owns its memory, so it may be moved to another thread as long as it is not in use.
+unsafe impl Send for StackRestoreGuard {}
+
 impl StackRestoreGuard {
     pub fn new(requested_size: usize) -> StackRestoreGuard {
         // For maximum portability we want to produce a stack that is aligned to a page and has
@@ -42,7 +47,9 @@ impl StackRestoreGuard {
                 mapping: new_stack as *mut u8,
                 page_size,
                 size_with_guard,
-                old_stack_limit: get_stack_limit(),
+                old_stack_limit: Some(get_stack_limit()),
+                #[cfg(feature = "valgrind")]
+                valgrind_stack_id: 0,
             };
             // We leave two guard pages without read/write access in our allocation.
             // There is one guard page below the stack and another above it.
@@ -73,6 +80,16 @@ impl StackRestoreGuard {
                 "mprotect/mmap failed: {}",
                 std::io::Error::last_os_error()
             );
+            #[cfg(feature = "valgrind")]
+            let guard = {
+                let mut guard = guard;
+                let (stack_base, stack_size) = guard.stack_area();
+                let stack_top = stack_base.add(stack_size);
+                crate::valgrind::make_mem_noaccess(guard.mapping, page_size);
+                crate::valgrind::make_mem_noaccess(stack_top, page_size);
+                guard.valgrind_stack_id = crate::valgrind::stack_register(stack_base, stack_top);
+                guard
+            };
             guard
         }
     }
@@ -86,16 +103,31 @@ impl StackRestoreGuard {
             )
         }
     }
+
+    /// Remembers the current stack limit, so that it is restored by `restore_stack_limit` or when
+    /// the guard is dropped. Used when the stack is reused for another switch.
+    pub fn save_stack_limit(&mut self) {
+        self.old_stack_limit = Some(get_stack_limit());
+    }
+
+    /// Restores the stack limit remembered when the guard was created or by `save_stack_limit`.
+    pub fn restore_stack_limit(&mut self) {
+        if let Some(old_stack_limit) = self.old_stack_limit.take() {
+            set_stack_limit(old_stack_limit);
+        }
+    }
 }
 
 impl Drop for StackRestoreGuard {
     fn drop(&mut self) {
+        #[cfg(feature = "valgrind")]
+        crate::valgrind::stack_deregister(self.valgrind_stack_id);
         unsafe {
             // FIXME: check the error code and decide what to do with it.
             // Perhaps a debug_assertion?
             libc::munmap(self.mapping as *mut std::ffi::c_void, self.size_with_guard);
         }
-        set_stack_limit(self.old_stack_limit);
+        self.restore_stack_limit();
     }
 }
 
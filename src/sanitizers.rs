@@ -0,0 +1,193 @@
+//! Annotations informing AddressSanitizer and ThreadSanitizer about switches between the stack of
+//! the thread and the stacks allocated by this library.
+//!
+//! Both sanitizers track the bounds of the stack that is currently executing. Without these
+//! annotations AddressSanitizer reports false positives once the stack pointer leaves the bounds it
+//! knows about, and ThreadSanitizer loses track of the shadow call stack. The build script enables
+//! them with `stacker_asan` and `stacker_tsan` when the corresponding `cfg(sanitize = "...")` is set.
+//!
+//! Without any of the sanitizers enabled everything in here compiles to nothing.
+
+#[cfg(any(stacker_asan, stacker_tsan))]
+use std::ffi::c_void;
+
+#[cfg(stacker_asan)]
+extern "C" {
+    fn __sanitizer_start_switch_fiber(
+        fake_stack_save: *mut *mut c_void,
+        bottom: *const c_void,
+        size: usize,
+    );
+    fn __sanitizer_finish_switch_fiber(
+        fake_stack_save: *mut c_void,
+        bottom_old: *mut *const c_void,
+        size_old: *mut usize,
+    );
+}
+
+#[cfg(stacker_tsan)]
+extern "C" {
+    fn __tsan_get_current_fiber() -> *mut c_void;
+    fn __tsan_create_fiber(flags: u32) -> *mut c_void;
+    fn __tsan_destroy_fiber(fiber: *mut c_void);
+    fn __tsan_switch_to_fiber(fiber: *mut c_void, flags: u32);
+}
+
+/// A stack that execution switches to and from, as far as the sanitizers are concerned.
+///
+/// For AddressSanitizer a switch from one fiber to another is announced with `start_switch` right
+/// before the stack pointer changes, and completed with `finish_switch` as the first thing on the
+/// other stack. ThreadSanitizer keeps a shadow call stack per fiber instead, which is switched by
+/// `make_current`. No function entered before that call may return after it.
+pub struct Fiber {
+    /// The fake frames of a suspended fiber, saved by AddressSanitizer.
+    #[cfg(stacker_asan)]
+    fake_stack: *mut c_void,
+    #[cfg(stacker_asan)]
+    bottom: *const c_void,
+    #[cfg(stacker_asan)]
+    size: usize,
+    /// The fiber to switch to, which is not necessarily the one created for this stack: a
+    /// suspended fiber may have been running on a stack segment it grew into.
+    #[cfg(stacker_tsan)]
+    fiber: *mut c_void,
+    /// The fiber created by `new`, if any.
+    #[cfg(stacker_tsan)]
+    created: *mut c_void,
+}
+
+impl Fiber {
+    /// The fiber currently running. Its bounds are learned by `finish_switch` on the fiber
+    /// switched to.
+    #[inline(always)]
+    pub fn current() -> Fiber {
+        Fiber {
+            #[cfg(stacker_asan)]
+            fake_stack: std::ptr::null_mut(),
+            #[cfg(stacker_asan)]
+            bottom: std::ptr::null(),
+            #[cfg(stacker_asan)]
+            size: 0,
+            #[cfg(stacker_tsan)]
+            fiber: unsafe { __tsan_get_current_fiber() },
+            #[cfg(stacker_tsan)]
+            created: std::ptr::null_mut(),
+        }
+    }
+
+    /// A new fiber running on the stack area starting at `stack_base` with `stack_size` bytes.
+    #[inline(always)]
+    #[allow(unused_variables)]
+    pub fn new(stack_base: *mut u8, stack_size: usize) -> Fiber {
+        #[cfg(stacker_tsan)]
+        let fiber = unsafe { __tsan_create_fiber(0) };
+        Fiber {
+            #[cfg(stacker_asan)]
+            fake_stack: std::ptr::null_mut(),
+            #[cfg(stacker_asan)]
+            bottom: stack_base as *const c_void,
+            #[cfg(stacker_asan)]
+            size: stack_size,
+            #[cfg(stacker_tsan)]
+            fiber,
+            #[cfg(stacker_tsan)]
+            created: fiber,
+        }
+    }
+
+    /// Announces a switch from this fiber to `to`. If `exiting` is set, this fiber is never
+    /// switched back to and anything left on its stack is discarded.
+    #[inline(always)]
+    #[allow(unused_variables)]
+    pub unsafe fn start_switch(&mut self, to: &Fiber, exiting: bool) {
+        #[cfg(stacker_asan)]
+        __sanitizer_start_switch_fiber(
+            if exiting {
+                std::ptr::null_mut()
+            } else {
+                &mut self.fake_stack
+            },
+            to.bottom,
+            to.size,
+        );
+        #[cfg(stacker_tsan)]
+        if !exiting {
+            self.fiber = __tsan_get_current_fiber();
+        }
+    }
+
+    /// Completes a switch from `from` to this fiber, remembering the bounds of the stack `from`
+    /// was running on.
+    #[inline(always)]
+    #[allow(unused_variables)]
+    pub unsafe fn finish_switch(&mut self, from: &mut Fiber) {
+        #[cfg(stacker_asan)]
+        __sanitizer_finish_switch_fiber(self.fake_stack, &mut from.bottom, &mut from.size);
+    }
+
+    /// Makes this fiber the one whose calls are tracked by ThreadSanitizer.
+    #[inline(always)]
+    pub unsafe fn make_current(&self) {
+        #[cfg(stacker_tsan)]
+        __tsan_switch_to_fiber(self.fiber, 0);
+    }
+}
+
+impl Drop for Fiber {
+    #[inline(always)]
+    fn drop(&mut self) {
+        #[cfg(stacker_tsan)]
+        if !self.created.is_null() {
+            unsafe { __tsan_destroy_fiber(self.created) };
+        }
+    }
+}
+
+/// A switch from the running fiber to a stack segment and back.
+///
+/// `new` announces the switch to the segment and `enter` completes it on the segment. Dropping the
+/// guard returned by `enter` announces the switch back, which dropping the `SegmentSwitch` completes
+/// on the original stack. Both happen even if the code running on the segment unwinds.
+pub struct SegmentSwitch {
+    current: Fiber,
+    segment: Fiber,
+}
+
+impl SegmentSwitch {
+    #[inline(always)]
+    pub unsafe fn new(stack_base: *mut u8, stack_size: usize) -> SegmentSwitch {
+        let mut current = Fiber::current();
+        let segment = Fiber::new(stack_base, stack_size);
+        current.start_switch(&segment, false);
+        SegmentSwitch { current, segment }
+    }
+
+    /// Completes the switch to the segment. ThreadSanitizer only switches fibers here, so that the
+    /// frames of `psm::on_stack` are entered and left on the same fiber.
+    #[inline(always)]
+    pub unsafe fn enter(&mut self) -> OnSegment<'_> {
+        self.segment.finish_switch(&mut self.current);
+        self.segment.make_current();
+        OnSegment(self)
+    }
+}
+
+impl Drop for SegmentSwitch {
+    #[inline(always)]
+    fn drop(&mut self) {
+        unsafe { self.current.finish_switch(&mut self.segment) };
+    }
+}
+
+/// Announces the switch back from the segment entered by `SegmentSwitch::enter` when dropped.
+pub struct OnSegment<'a>(&'a mut SegmentSwitch);
+
+impl Drop for OnSegment<'_> {
+    #[inline(always)]
+    fn drop(&mut self) {
+        unsafe {
+            self.0.current.make_current();
+            self.0.segment.start_switch(&self.0.current, true);
+        }
+    }
+}
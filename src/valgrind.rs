@@ -0,0 +1,70 @@
+//! Valgrind client requests, used to tell Valgrind about the stacks allocated by this library.
+//!
+//! Client requests are special instruction sequences that do nothing when run natively, but are
+//! recognised by Valgrind. They are issued directly instead of linking to anything shipped with
+//! Valgrind. On architectures not listed here the requests do nothing.
+
+const STACK_REGISTER: usize = 0x1501;
+const STACK_DEREGISTER: usize = 0x1502;
+/// `VG_USERREQ_TOOL_BASE('M', 'C')`, the first of the memcheck requests.
+const MAKE_MEM_NOACCESS: usize = (b'M' as usize) << 24 | (b'C' as usize) << 16;
+
+/// Issues the client request with the given arguments, returning `default` when not running under
+/// Valgrind.
+#[inline(always)]
+#[allow(unused_variables)]
+unsafe fn client_request(default: usize, request: usize, args: [usize; 5]) -> usize {
+    #[allow(unused_mut)]
+    let mut result = default;
+    let block = [request, args[0], args[1], args[2], args[3], args[4]];
+    #[cfg(target_arch = "x86_64")]
+    std::arch::asm!(
+        "rol rdi, 3",
+        "rol rdi, 13",
+        "rol rdi, 61",
+        "rol rdi, 51",
+        "xchg rbx, rbx",
+        in("rax") block.as_ptr(),
+        inout("rdx") result,
+        options(nostack),
+    );
+    #[cfg(target_arch = "x86")]
+    std::arch::asm!(
+        "rol edi, 3",
+        "rol edi, 13",
+        "rol edi, 29",
+        "rol edi, 19",
+        "xchg ebx, ebx",
+        in("eax") block.as_ptr(),
+        inout("edx") result,
+        options(nostack),
+    );
+    #[cfg(target_arch = "aarch64")]
+    std::arch::asm!(
+        "ror x12, x12, #3",
+        "ror x12, x12, #13",
+        "ror x12, x12, #51",
+        "ror x12, x12, #61",
+        "orr x10, x10, x10",
+        in("x4") block.as_ptr(),
+        inout("x3") result,
+        options(nostack, preserves_flags),
+    );
+    result
+}
+
+/// Registers the memory between `start` and `end` as a stack, returning the identifier to pass to
+/// `stack_deregister`.
+pub fn stack_register(start: *mut u8, end: *mut u8) -> usize {
+    unsafe { client_request(0, STACK_REGISTER, [start as usize, end as usize, 0, 0, 0]) }
+}
+
+/// Forgets the stack registered under `id`.
+pub fn stack_deregister(id: usize) {
+    unsafe { client_request(0, STACK_DEREGISTER, [id, 0, 0, 0, 0]) };
+}
+
+/// Marks `len` bytes starting at `addr` as not accessible by the program.
+pub fn make_mem_noaccess(addr: *mut u8, len: usize) {
+    unsafe { client_request(0, MAKE_MEM_NOACCESS, [addr as usize, len, 0, 0, 0]) };
+}
@@ -0,0 +1,68 @@
+extern crate stacker;
+
+use std::backtrace::Backtrace;
+
+const STACK_SIZE: usize = 1024 * 1024;
+
+#[inline(never)]
+fn outer_grow_caller() -> String {
+    stacker::grow(STACK_SIZE, middle_grow_caller)
+}
+
+#[inline(never)]
+fn middle_grow_caller() -> String {
+    stacker::grow(STACK_SIZE, inner_grow_caller)
+}
+
+#[inline(never)]
+fn inner_grow_caller() -> String {
+    stacker::grow(STACK_SIZE, || Backtrace::force_capture().to_string())
+}
+
+// Frames on the original stack are only reachable by the unwinder when the callback runs on a
+// stack switched to by psm, which is neither the case on Windows nor under Miri. Miri cannot
+// symbolize backtraces with isolation enabled anyway.
+#[test]
+#[cfg_attr(any(windows, miri, stacker_no_growth), ignore)]
+fn backtrace_through_nested_grow() {
+    let trace = outer_grow_caller();
+    for caller in [
+        "outer_grow_caller",
+        "middle_grow_caller",
+        "inner_grow_caller",
+    ] {
+        assert!(
+            trace.contains(caller),
+            "`{}` is missing from the backtrace:\n{}",
+            caller,
+            trace
+        );
+    }
+}
+
+#[inline(never)]
+fn recurse(n: usize) -> String {
+    if n == 0 {
+        Backtrace::force_capture().to_string()
+    } else {
+        // The red zone is larger than any stack, so that every call grows the stack.
+        stacker::maybe_grow(1 << 30, 64 * 1024, || recurse(n - 1))
+    }
+}
+
+#[inline(never)]
+fn maybe_grow_caller() -> String {
+    // Keep the call from being turned into a tail call, which would remove this frame.
+    std::hint::black_box(recurse(8))
+}
+
+#[test]
+#[cfg_attr(any(windows, miri, stacker_no_growth), ignore)] // See above
+fn backtrace_through_maybe_grow() {
+    let trace = maybe_grow_caller();
+    assert!(
+        trace.contains("maybe_grow_caller"),
+        "`maybe_grow_caller` is missing from the backtrace:\n{}",
+        trace
+    );
+}
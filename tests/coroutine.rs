@@ -0,0 +1,183 @@
+// Coroutines are only available where psm can switch contexts.
+#![cfg(stacker_coroutines)]
+
+extern crate stacker;
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+use stacker::{Coroutine, CoroutineState, Generator, Yielder};
+
+#[test]
+fn resume_and_yield() {
+    let mut coroutine = Coroutine::new(64 * 1024, |yielder| {
+        for i in 0..3 {
+            yielder.yield_(i);
+        }
+        "done"
+    });
+    assert_eq!(coroutine.resume(), CoroutineState::Yielded(0));
+    assert_eq!(coroutine.resume(), CoroutineState::Yielded(1));
+    assert_eq!(coroutine.resume(), CoroutineState::Yielded(2));
+    assert!(!coroutine.is_complete());
+    assert_eq!(coroutine.resume(), CoroutineState::Complete("done"));
+    assert!(coroutine.is_complete());
+}
+
+#[test]
+fn borrows_environment() {
+    let values = vec![1, 2, 3];
+    let doubled = Generator::new(64 * 1024, |yielder| {
+        for v in &values {
+            yielder.yield_(v * 2);
+        }
+    });
+    assert_eq!(doubled.collect::<Vec<_>>(), [2, 4, 6]);
+}
+
+enum Tree {
+    Leaf(u32),
+    Node(Box<Tree>, Box<Tree>),
+}
+
+fn walk(tree: &Tree, yielder: &Yielder<u32>) {
+    match tree {
+        Tree::Leaf(v) => yielder.yield_(*v),
+        Tree::Node(l, r) => stacker::maybe_grow(32 * 1024, 1024 * 1024, || {
+            walk(l, yielder);
+            walk(r, yielder);
+        }),
+    }
+}
+
+#[test]
+fn deep_traversal() {
+    // A degenerate tree, deep enough to overflow the coroutine's stack without growing it.
+    let depth = 100_000;
+    let mut tree = Tree::Leaf(0);
+    for i in 1..=depth {
+        tree = Tree::Node(Box::new(tree), Box::new(Tree::Leaf(i)));
+    }
+    let leaves = Generator::new(64 * 1024, |yielder| walk(&tree, yielder));
+    assert!(leaves.eq(0..=depth));
+    // Dropping a deep tree recurses as well.
+    stacker::grow(64 * 1024 * 1024, || drop(tree));
+}
+
+#[test]
+fn stack_limit_on_switch() {
+    const STACK_SIZE: usize = 256 * 1024;
+    let outer = stacker::remaining_stack().unwrap();
+    let mut coroutine = Coroutine::new(STACK_SIZE, |yielder| {
+        yielder.yield_(stacker::remaining_stack().unwrap());
+        stacker::grow(1024 * 1024, || {
+            yielder.yield_(stacker::remaining_stack().unwrap());
+        });
+        stacker::remaining_stack().unwrap()
+    });
+    match coroutine.resume() {
+        CoroutineState::Yielded(remaining) => assert!(remaining < STACK_SIZE),
+        _ => unreachable!(),
+    }
+    assert!(stacker::remaining_stack().unwrap().abs_diff(outer) < 4096);
+    match coroutine.resume() {
+        CoroutineState::Yielded(remaining) => {
+            assert!(remaining > STACK_SIZE && remaining < 1024 * 1024)
+        }
+        _ => unreachable!(),
+    }
+    assert!(stacker::remaining_stack().unwrap().abs_diff(outer) < 4096);
+    match coroutine.resume() {
+        CoroutineState::Complete(remaining) => assert!(remaining < STACK_SIZE),
+        _ => unreachable!(),
+    }
+    assert!(stacker::remaining_stack().unwrap().abs_diff(outer) < 4096);
+}
+
+#[test]
+fn panic_propagates_to_resumer() {
+    let mut coroutine = Coroutine::new(64 * 1024, |yielder: &Yielder<()>| {
+        yielder.yield_(());
+        panic!("bottom");
+    });
+    assert_eq!(coroutine.resume(), CoroutineState::Yielded(()));
+    let panic_result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        coroutine.resume();
+    }));
+    assert_eq!(
+        panic_result.unwrap_err().downcast_ref::<&str>(),
+        Some(&"bottom")
+    );
+    assert!(coroutine.is_complete());
+}
+
+#[test]
+#[should_panic(expected = "coroutine resumed after completion")]
+fn resume_after_completion() {
+    let mut coroutine = Coroutine::new(64 * 1024, |_: &Yielder<()>| {});
+    assert_eq!(coroutine.resume(), CoroutineState::Complete(()));
+    coroutine.resume();
+}
+
+struct SetOnDrop(Rc<Cell<bool>>);
+
+impl Drop for SetOnDrop {
+    fn drop(&mut self) {
+        self.0.set(true);
+    }
+}
+
+#[test]
+fn drop_suspended_unwinds_stack() {
+    let dropped = Rc::new(Cell::new(false));
+    let on_stack = SetOnDrop(dropped.clone());
+    let mut coroutine = Coroutine::new(64 * 1024, move |yielder| {
+        let _on_stack = on_stack;
+        yielder.yield_(());
+        unreachable!();
+    });
+    assert_eq!(coroutine.resume(), CoroutineState::Yielded(()));
+    assert!(!dropped.get());
+    drop(coroutine);
+    assert!(dropped.get());
+}
+
+#[test]
+#[should_panic(expected = "coroutine yielded while it was being dropped")]
+fn drop_suspended_yielding_again() {
+    let mut coroutine = Coroutine::new(64 * 1024, |yielder| {
+        let cancelled = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            yielder.yield_(());
+        }));
+        assert!(cancelled.is_err());
+        yielder.yield_(());
+    });
+    assert_eq!(coroutine.resume(), CoroutineState::Yielded(()));
+    drop(coroutine);
+}
+
+#[test]
+fn drop_unstarted() {
+    let dropped = Rc::new(Cell::new(false));
+    let on_stack = SetOnDrop(dropped.clone());
+    let coroutine = Coroutine::new(64 * 1024, move |_: &Yielder<()>| drop(on_stack));
+    drop(coroutine);
+    assert!(dropped.get());
+}
+
+#[test]
+fn nested_coroutines() {
+    let outer = Generator::new(64 * 1024, |yielder| {
+        for i in 0..3 {
+            let inner = Generator::new(64 * 1024, |inner_yielder| {
+                for j in 0..3 {
+                    inner_yielder.yield_(i * 10 + j);
+                }
+            });
+            for v in inner {
+                yielder.yield_(v);
+            }
+        }
+    });
+    assert_eq!(outer.collect::<Vec<_>>(), [0, 1, 2, 10, 11, 12, 20, 21, 22]);
+}
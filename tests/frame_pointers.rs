@@ -0,0 +1,114 @@
+//! These tests need the frame pointer chain to be maintained, so they are ignored unless the code is
+//! built with `-C force-frame-pointers=yes` or for a target that keeps frame pointers by default.
+
+extern crate stacker;
+
+use std::cell::Cell;
+use std::hint::black_box;
+
+const STACK_SIZE: usize = 1024 * 1024;
+
+/// The frame pointer of the calling function.
+#[inline(always)]
+fn frame_pointer() -> Option<usize> {
+    #[allow(unused_mut, unused_assignments)]
+    let mut fp = None;
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        let value: usize;
+        std::arch::asm!("mov {}, rbp", out(reg) value, options(nomem, nostack, preserves_flags));
+        fp = Some(value);
+    }
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        let value: usize;
+        std::arch::asm!("mov {}, x29", out(reg) value, options(nomem, nostack, preserves_flags));
+        fp = Some(value);
+    }
+    #[cfg(target_arch = "riscv64")]
+    unsafe {
+        let value: usize;
+        std::arch::asm!("mv {}, s0", out(reg) value, options(nomem, nostack, preserves_flags));
+        fp = Some(value);
+    }
+    fp
+}
+
+/// The address of the slot holding the caller's frame pointer in the frame record `fp` points to.
+fn previous_frame_slot(fp: usize) -> usize {
+    if cfg!(target_arch = "riscv64") {
+        // The frame pointer points just past the frame record.
+        fp - 2 * std::mem::size_of::<usize>()
+    } else {
+        fp
+    }
+}
+
+/// Frame pointers encountered when walking the chain from the innermost callback.
+#[inline(never)]
+fn walk_frame_pointers(stop_at: usize) -> Vec<usize> {
+    let mut frames = Vec::new();
+    let mut fp = frame_pointer().unwrap();
+    while fp != 0 && fp != stop_at && frames.len() < 64 {
+        frames.push(fp);
+        fp = unsafe { *(previous_frame_slot(fp) as *const usize) };
+    }
+    frames.push(fp);
+    frames
+}
+
+thread_local! {
+    static OUTER_FP: Cell<usize> = const { Cell::new(0) };
+    static MIDDLE_FP: Cell<usize> = const { Cell::new(0) };
+}
+
+#[inline(never)]
+fn outer_grow_caller() -> Vec<usize> {
+    // Read outside of the closure, which may have a frame of its own.
+    let fp = frame_pointer().unwrap();
+    OUTER_FP.with(|cell| cell.set(fp));
+    black_box(stacker::grow(STACK_SIZE, middle_grow_caller))
+}
+
+#[inline(never)]
+fn middle_grow_caller() -> Vec<usize> {
+    let fp = frame_pointer().unwrap();
+    MIDDLE_FP.with(|cell| cell.set(fp));
+    black_box(stacker::grow(STACK_SIZE, || {
+        walk_frame_pointers(OUTER_FP.with(Cell::get))
+    }))
+}
+
+// Frame records on the original stack are only reachable when the callback runs on a stack switched
+// to by psm, which is neither the case on Windows nor under Miri.
+#[test]
+#[cfg_attr(
+    any(
+        windows,
+        miri,
+        stacker_no_growth,
+        not(stacker_frame_pointers),
+        not(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "riscv64"
+        )),
+    ),
+    ignore
+)]
+fn frame_pointer_chain_through_nested_grow() {
+    let frames = outer_grow_caller();
+    let (outer, middle) = (OUTER_FP.with(Cell::get), MIDDLE_FP.with(Cell::get));
+    assert!(
+        frames.contains(&middle),
+        "frame of `middle_grow_caller` ({:#x}) is missing from the chain: {:x?}",
+        middle,
+        frames
+    );
+    assert_eq!(
+        frames.last(),
+        Some(&outer),
+        "frame of `outer_grow_caller` is missing from the chain: {:x?}",
+        frames
+    );
+}
@@ -0,0 +1,123 @@
+extern crate stacker;
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+fn noop_waker() -> Waker {
+    fn clone(_: *const ()) -> RawWaker {
+        RawWaker::new(std::ptr::null(), &VTABLE)
+    }
+    fn noop(_: *const ()) {}
+    static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+    unsafe { Waker::from_raw(RawWaker::new(std::ptr::null(), &VTABLE)) }
+}
+
+fn poll_once<F: Future + Unpin>(future: &mut F) -> Poll<F::Output> {
+    let waker = noop_waker();
+    Pin::new(future).poll(&mut Context::from_waker(&waker))
+}
+
+fn block_on<F: Future + Unpin>(mut future: F) -> F::Output {
+    loop {
+        if let Poll::Ready(output) = poll_once(&mut future) {
+            return output;
+        }
+    }
+}
+
+/// Returns `Pending` the first `n` times it is polled.
+struct YieldN(usize);
+
+impl Future for YieldN {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 == 0 {
+            Poll::Ready(())
+        } else {
+            self.0 -= 1;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
+
+#[inline(never)]
+fn __stacker_black_box(_: *const u8) {}
+
+fn sum(n: u64, keep_segment: bool) -> Pin<Box<dyn Future<Output = u64> + Send>> {
+    Box::pin(
+        stacker::maybe_grow_async(64 * 1024, 1024 * 1024, async move {
+            let s = [0u8; 1024];
+            __stacker_black_box(s.as_ptr());
+            if n == 0 {
+                YieldN(2).await;
+                0
+            } else {
+                n + sum(n - 1, keep_segment).await
+            }
+        })
+        .keep_segment(keep_segment),
+    )
+}
+
+#[test]
+#[cfg_attr(stacker_no_growth, ignore)]
+#[cfg_attr(miri, ignore)] // Too slow under Miri's interpreter
+fn deep() {
+    let limit = if cfg!(target_arch = "wasm32") {
+        2000
+    } else {
+        20_000
+    };
+    assert_eq!(block_on(sum(limit, false)), limit * (limit + 1) / 2);
+    assert_eq!(block_on(sum(limit, true)), limit * (limit + 1) / 2);
+}
+
+#[test]
+#[cfg_attr(stacker_no_growth, ignore)]
+#[cfg_attr(target_arch = "wasm32", ignore)]
+#[cfg_attr(miri, ignore)] // Too slow under Miri's interpreter
+fn moved_between_threads() {
+    let limit = 20_000;
+    let mut future = sum(limit, true);
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        assert!(poll_once(&mut future).is_pending());
+        tx.send(future).unwrap();
+    })
+    .join()
+    .unwrap();
+    let future = rx.recv().unwrap();
+    let result = std::thread::spawn(move || block_on(future)).join().unwrap();
+    assert_eq!(result, limit * (limit + 1) / 2);
+}
+
+#[test]
+#[cfg_attr(target_arch = "wasm32", ignore)]
+fn panic_propagates() {
+    let mut future = Box::pin(
+        stacker::maybe_grow_async(usize::MAX, 64 * 1024, async {
+            YieldN(1).await;
+            panic!("bottom");
+        })
+        .keep_segment(true),
+    );
+    assert!(poll_once(&mut future).is_pending());
+    let panic_result =
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| poll_once(&mut future)));
+    assert!(panic_result.is_err());
+}
+
+#[test]
+#[cfg_attr(miri, ignore)] // The stack limit is unknown under Miri
+#[cfg_attr(stacker_no_growth, ignore)]
+fn polls_on_new_stack() {
+    const STACK_SIZE: usize = 1024 * 1024;
+    let future = stacker::maybe_grow_async(usize::MAX, STACK_SIZE, async {
+        stacker::remaining_stack().unwrap()
+    });
+    let remaining = block_on(Box::pin(future));
+    assert!(remaining < 2 * STACK_SIZE);
+}
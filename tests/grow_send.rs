@@ -0,0 +1,71 @@
+extern crate stacker;
+
+use stacker::Growth;
+
+#[inline(never)]
+fn __stacker_black_box(_: *const u8) {}
+
+/// How `grow_send` provided the stack its closure ran on.
+fn observed_growth() -> Growth {
+    let thread = std::thread::current().id();
+    let outer = 0u8;
+    let outer = &outer as *const u8 as usize;
+    stacker::grow_send(1024 * 1024, || {
+        let inner = 0u8;
+        let inner = &inner as *const u8 as usize;
+        if std::thread::current().id() != thread {
+            Growth::Thread
+        } else if outer.abs_diff(inner) > 64 * 1024 {
+            Growth::Native
+        } else {
+            Growth::NoOp
+        }
+    })
+}
+
+#[test]
+#[cfg_attr(target_arch = "wasm32", ignore)]
+fn capabilities() {
+    assert_eq!(stacker::capabilities().grow_send, observed_growth());
+}
+
+#[test]
+#[cfg_attr(target_arch = "wasm32", ignore)]
+fn deep() {
+    fn foo(n: usize, s: &mut [u8]) -> usize {
+        __stacker_black_box(s.as_ptr());
+        if n > 0 {
+            stacker::maybe_grow_send(64 * 1024, 1024 * 1024, || {
+                let mut s = [0u8; 1024];
+                let r = foo(n - 1, &mut s) + 1;
+                __stacker_black_box(s.as_ptr());
+                r
+            })
+        } else {
+            0
+        }
+    }
+
+    let limit = if cfg!(miri) { 2000 } else { 256 * 1024 };
+    assert_eq!(foo(limit, &mut []), limit);
+}
+
+#[test]
+#[cfg_attr(target_arch = "wasm32", ignore)]
+fn grow_send_result() {
+    let s = String::from("stacker");
+    let len = stacker::grow_send(64 * 1024, || s.len());
+    assert_eq!(len, 7);
+}
+
+#[test]
+#[cfg_attr(target_arch = "wasm32", ignore)]
+fn grow_send_panic() {
+    let panic_result = std::panic::catch_unwind(|| {
+        stacker::grow_send(64 * 1024, || {
+            stacker::grow_send(64 * 1024, || panic!("bottom"));
+        })
+    });
+    let payload = panic_result.unwrap_err();
+    assert_eq!(payload.downcast_ref::<&str>(), Some(&"bottom"));
+}
@@ -0,0 +1,91 @@
+#![cfg(all(unix, not(miri)))]
+
+extern crate libc;
+extern crate stacker;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+const ALT_STACK_SIZE: usize = 256 * 1024;
+
+// Signal dispositions are process-wide, so the tests must not install handlers concurrently.
+static LOCK: Mutex<()> = Mutex::new(());
+static REMAINING: AtomicUsize = AtomicUsize::new(0);
+static DEPTH: AtomicUsize = AtomicUsize::new(0);
+
+#[inline(never)]
+fn __stacker_black_box(_: *const u8) {}
+
+fn recurse(n: usize) {
+    let s = [0u8; 1024];
+    __stacker_black_box(s.as_ptr());
+    if n > 0 {
+        stacker::maybe_grow(32 * 1024, 1024 * 1024, || recurse(n - 1));
+    }
+    __stacker_black_box(s.as_ptr());
+}
+
+extern "C" fn handler(_: std::os::raw::c_int) {
+    REMAINING.store(stacker::remaining_stack().unwrap_or(0), Ordering::SeqCst);
+    // Would overflow the alternate stack if the remaining space was computed against the limit of
+    // the thread's stack.
+    recurse(DEPTH.load(Ordering::SeqCst));
+}
+
+/// Runs `handler` on an alternate signal stack of this thread.
+fn raise_on_alt_stack(depth: usize) -> usize {
+    let _lock = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    // Makes `remaining_stack` safe to call from the handler.
+    stacker::init_stack_limit();
+    unsafe {
+        let mut alt_stack = vec![0u8; ALT_STACK_SIZE];
+        DEPTH.store(depth, Ordering::SeqCst);
+        let new = libc::stack_t {
+            ss_sp: alt_stack.as_mut_ptr() as *mut _,
+            ss_flags: 0,
+            ss_size: ALT_STACK_SIZE,
+        };
+        let mut old = std::mem::zeroed::<libc::stack_t>();
+        assert_eq!(libc::sigaltstack(&new, &mut old), 0);
+
+        let mut action = std::mem::zeroed::<libc::sigaction>();
+        action.sa_sigaction = handler as extern "C" fn(std::os::raw::c_int) as libc::sighandler_t;
+        action.sa_flags = libc::SA_ONSTACK;
+        let mut old_action = std::mem::zeroed::<libc::sigaction>();
+        assert_eq!(libc::sigaction(libc::SIGUSR1, &action, &mut old_action), 0);
+        assert_eq!(libc::raise(libc::SIGUSR1), 0);
+        assert_eq!(
+            libc::sigaction(libc::SIGUSR1, &old_action, std::ptr::null_mut()),
+            0
+        );
+
+        assert_eq!(libc::sigaltstack(&old, std::ptr::null_mut()), 0);
+        drop(alt_stack);
+        REMAINING.load(Ordering::SeqCst)
+    }
+}
+
+#[test]
+fn remaining_stack_on_alt_stack() {
+    std::thread::spawn(|| {
+        let remaining = raise_on_alt_stack(0);
+        assert!(remaining > 0);
+        assert!(
+            remaining < ALT_STACK_SIZE,
+            "remaining stack {} is larger than the alternate stack",
+            remaining
+        );
+    })
+    .join()
+    .unwrap();
+}
+
+#[test]
+#[cfg_attr(stacker_no_growth, ignore)]
+fn maybe_grow_on_alt_stack() {
+    std::thread::spawn(|| {
+        raise_on_alt_stack(1024);
+    })
+    .join()
+    .unwrap();
+}
@@ -7,6 +7,7 @@ use std::thread;
 fn __stacker_black_box(_: *const u8) {}
 
 #[test]
+#[cfg_attr(stacker_no_growth, ignore)]
 fn deep() {
     fn foo(n: usize, s: &mut [u8]) {
         __stacker_black_box(s.as_ptr());
@@ -30,6 +31,7 @@ fn deep() {
 }
 
 #[test]
+#[cfg_attr(stacker_no_growth, ignore)]
 #[cfg_attr(target_arch = "wasm32", ignore)]
 #[cfg_attr(miri, ignore)] // Too slow under Miri's interpreter
 fn panic() {
@@ -94,3 +96,20 @@ fn catch_panic_leaf() {
         assert!(panic_result.is_err());
     });
 }
+
+#[test]
+#[cfg_attr(target_arch = "wasm32", ignore)]
+#[cfg_attr(miri, ignore)] // The stack limit is unknown under Miri
+fn remaining_stack_in_grow() {
+    const STACK_SIZE: usize = 1024 * 1024;
+    stacker::grow(STACK_SIZE, || {
+        let remaining = stacker::remaining_stack().unwrap();
+        assert!(remaining > 0 && remaining < 2 * STACK_SIZE);
+        stacker::grow(STACK_SIZE, || {
+            let inner = stacker::remaining_stack().unwrap();
+            assert!(inner > 0 && inner < 2 * STACK_SIZE);
+        });
+        let after = stacker::remaining_stack().unwrap();
+        assert!(after.abs_diff(remaining) < 64 * 1024);
+    });
+}
@@ -0,0 +1,104 @@
+extern crate stacker;
+
+use stacker::StackCtx;
+
+#[inline(never)]
+fn __stacker_black_box(_: *const u8) {}
+
+enum Tree {
+    Leaf,
+    Node(Box<Tree>, Box<Tree>),
+}
+
+fn depth(tree: &Tree, cx: StackCtx) -> usize {
+    match tree {
+        Tree::Leaf => 0,
+        Tree::Node(l, r) => cx.maybe_grow(32 * 1024, 1024 * 1024, |cx| {
+            1 + depth(l, cx).max(depth(r, cx))
+        }),
+    }
+}
+
+#[test]
+fn tree_depth() {
+    let tree = Tree::Node(
+        Box::new(Tree::Node(Box::new(Tree::Leaf), Box::new(Tree::Leaf))),
+        Box::new(Tree::Leaf),
+    );
+    assert_eq!(StackCtx::with(|cx| depth(&tree, cx)), 2);
+}
+
+#[test]
+#[cfg_attr(stacker_no_growth, ignore)]
+#[cfg_attr(miri, ignore)] // Too slow under Miri's interpreter
+fn deep() {
+    fn foo(n: usize, s: &mut [u8], cx: StackCtx) {
+        __stacker_black_box(s.as_ptr());
+        if n > 0 {
+            cx.maybe_grow(64 * 1024, 1024 * 1024, |cx| {
+                let mut s = [0u8; 1024];
+                foo(n - 1, &mut s, cx);
+                __stacker_black_box(s.as_ptr());
+            })
+        }
+    }
+
+    let limit = if cfg!(target_arch = "wasm32") {
+        2000
+    } else {
+        256 * 1024
+    };
+    StackCtx::with(|cx| foo(limit, &mut [], cx));
+}
+
+#[test]
+#[cfg_attr(target_arch = "wasm32", ignore)]
+fn catch_panic() {
+    fn recursive(n: usize, cx: StackCtx) -> usize {
+        if n > 0 {
+            cx.grow(64 * 1024, |cx| recursive(n - 1, cx) + 1)
+        } else {
+            panic!("bottom")
+        }
+    }
+    let panic_result = std::panic::catch_unwind(|| StackCtx::with(|cx| recursive(100, cx)));
+    assert!(panic_result.is_err());
+}
+
+#[test]
+#[cfg_attr(target_arch = "wasm32", ignore)]
+#[cfg_attr(miri, ignore)] // The stack limit is unknown under Miri
+fn remaining_stack_matches() {
+    StackCtx::with(|cx| {
+        let a = cx.remaining_stack().unwrap();
+        let b = stacker::remaining_stack().unwrap();
+        assert!(a.abs_diff(b) < 4096);
+        cx.grow(1024 * 1024, |cx| {
+            let a = cx.remaining_stack().unwrap();
+            let b = stacker::remaining_stack().unwrap();
+            assert!(a.abs_diff(b) < 4096);
+            assert!(a < 2 * 1024 * 1024);
+        });
+    });
+}
+
+#[test]
+#[cfg_attr(target_arch = "wasm32", ignore)]
+#[cfg_attr(miri, ignore)] // The stack limit is unknown under Miri
+fn outer_token_on_new_stack() {
+    StackCtx::with(|outer| {
+        outer.grow(1024 * 1024, |_| {
+            // The token of the original stack knows nothing about this one, so it has to look the
+            // bounds up again rather than report that no stack is left.
+            assert!(outer.remaining_stack().unwrap() > 512 * 1024);
+            let local = 0u8;
+            let sp = &local as *const u8 as usize;
+            outer.maybe_grow(32 * 1024, 1024 * 1024, |cx| {
+                let nested_local = 0u8;
+                let nested_sp = &nested_local as *const u8 as usize;
+                assert!(sp.abs_diff(nested_sp) < 4096, "switched stacks needlessly");
+                assert!(cx.remaining_stack().unwrap() > 512 * 1024);
+            });
+        });
+    });
+}
@@ -0,0 +1,93 @@
+extern crate stacker;
+
+use std::cell::RefCell;
+
+#[inline(never)]
+fn __stacker_black_box(_: *const u8) {}
+
+fn recurse(n: usize) -> usize {
+    let s = [0u8; 1024];
+    __stacker_black_box(s.as_ptr());
+    if n > 0 {
+        stacker::maybe_grow(32 * 1024, 1024 * 1024, || recurse(n - 1) + 1)
+    } else {
+        0
+    }
+}
+
+struct RecurseOnDrop(usize);
+
+impl Drop for RecurseOnDrop {
+    fn drop(&mut self) {
+        assert_eq!(recurse(self.0), self.0);
+    }
+}
+
+/// A linked list that is long enough to overflow the stack when dropped naively.
+struct List(Option<Box<List>>);
+
+impl Drop for List {
+    fn drop(&mut self) {
+        if let Some(next) = self.0.take() {
+            stacker::maybe_grow(32 * 1024, 1024 * 1024, || drop(next));
+        }
+    }
+}
+
+fn list(len: usize) -> List {
+    let mut list = List(None);
+    for _ in 0..len {
+        list = List(Some(Box::new(list)));
+    }
+    list
+}
+
+#[test]
+#[cfg_attr(stacker_no_growth, ignore)]
+#[cfg_attr(target_arch = "wasm32", ignore)]
+#[cfg_attr(miri, ignore)] // Too slow under Miri's interpreter
+fn recursion_in_tls_destructor() {
+    thread_local! {
+        static RECURSE: RefCell<Option<RecurseOnDrop>> = const { RefCell::new(None) };
+    }
+    std::thread::spawn(|| {
+        RECURSE.with(|r| *r.borrow_mut() = Some(RecurseOnDrop(10_000)));
+    })
+    .join()
+    .unwrap();
+}
+
+#[test]
+#[cfg_attr(stacker_no_growth, ignore)]
+#[cfg_attr(target_arch = "wasm32", ignore)]
+#[cfg_attr(miri, ignore)] // Too slow under Miri's interpreter
+fn drop_deep_list_in_tls_destructor() {
+    thread_local! {
+        static LIST: RefCell<Option<List>> = const { RefCell::new(None) };
+    }
+    std::thread::spawn(|| {
+        LIST.with(|l| *l.borrow_mut() = Some(list(200_000)));
+    })
+    .join()
+    .unwrap();
+}
+
+#[test]
+#[cfg_attr(stacker_no_growth, ignore)]
+#[cfg_attr(target_arch = "wasm32", ignore)]
+#[cfg_attr(miri, ignore)] // Too slow under Miri's interpreter
+fn grow_in_tls_destructor_after_stacker_use() {
+    thread_local! {
+        static RECURSE: RefCell<Option<RecurseOnDrop>> = const { RefCell::new(None) };
+    }
+    std::thread::spawn(|| {
+        // Initialise the stack limit first, so that the destructor sees the thread's state.
+        assert_eq!(recurse(100), 100);
+        RECURSE.with(|r| *r.borrow_mut() = Some(RecurseOnDrop(10_000)));
+        stacker::grow(64 * 1024, || {
+            assert_eq!(recurse(100), 100);
+        });
+    })
+    .join()
+    .unwrap();
+}